@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use paf::PafRecord;
+use rust_lapper::{Interval, Lapper};
+
+use crate::{cigar::get_seq_divergence, cli::DivergenceMetric, io::Monomer};
+
+pub const ABUNDANCE_TSV_HEADER: &str =
+    "motif\tmotif_len\taligned_bp\tn_alignments\tmean_de\tperiods";
+
+/// Abundance of an `srf` motif in an assembly from its alignments.
+#[derive(Debug, Default, Clone)]
+pub struct MotifAbundance {
+    /// Aligned intervals on the assembly per query.
+    aligned: BTreeMap<String, Vec<Interval<u32, ()>>>,
+    pub n_alignments: usize,
+    de_sum: f64,
+    n_de: usize,
+}
+
+impl MotifAbundance {
+    /// Add an alignment. Its divergence is from the `de` tag or computed from the `cg` extended cigar string, if either.
+    pub fn add(&mut self, rec: &PafRecord) -> eyre::Result<()> {
+        self.aligned
+            .entry(rec.query_name().to_owned())
+            .or_default()
+            .push(Interval {
+                start: rec.query_start(),
+                stop: rec.query_end(),
+                val: (),
+            });
+        self.n_alignments += 1;
+        if rec.de().is_some() || rec.cg().is_some() {
+            self.de_sum += get_seq_divergence(rec, DivergenceMetric::GapCompressed)?;
+            self.n_de += 1;
+        }
+        Ok(())
+    }
+
+    /// Assembly base pairs aligned to the motif. Overlapping alignments are only counted once.
+    pub fn aligned_bp(&self) -> u64 {
+        self.aligned
+            .values()
+            .map(|itvs| {
+                let mut itvs = Lapper::new(itvs.clone());
+                itvs.merge_overlaps();
+                itvs.iter()
+                    .map(|itv| (itv.stop - itv.start) as u64)
+                    .sum::<u64>()
+            })
+            .sum()
+    }
+
+    /// Mean gap-compressed divergence of alignments with a `de` tag or cigar.
+    pub fn mean_de(&self) -> Option<f64> {
+        (self.n_de != 0).then(|| self.de_sum / self.n_de as f64)
+    }
+}
+
+/// Base pairs of the motif covered by `trf` monomers of each period. ex. `171:1304,342:1376`
+/// * Ordered from most to least base pairs.
+pub fn period_composition(monomers: &Lapper<u32, Monomer>) -> String {
+    let mut period_bp: BTreeMap<u32, u64> = BTreeMap::new();
+    for monomer in monomers.iter() {
+        *period_bp.entry(monomer.val.trf_period).or_default() +=
+            (monomer.stop - monomer.start) as u64;
+    }
+    if period_bp.is_empty() {
+        return String::from(".");
+    }
+    period_bp
+        .into_iter()
+        .sorted_by(|(p1, bp1), (p2, bp2)| bp2.cmp(bp1).then(p1.cmp(p2)))
+        .map(|(period, bp)| format!("{period}:{bp}"))
+        .join(",")
+}
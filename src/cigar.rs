@@ -1,12 +1,14 @@
-use eyre::{ContextCompat, bail};
+use eyre::{Context, ContextCompat, bail};
 use itertools::Itertools;
 use paf::PafRecord;
 use rust_lapper::Interval;
 
+use crate::cli::DivergenceMetric;
+
 type IntervalPair = (Interval<u32, ()>, Interval<u32, ()>);
 
 // \*|([0-9]+[MIDNSHP=X])+
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CigarToken {
     Number,
     Match,
@@ -52,15 +54,17 @@ pub enum CigarOp {
 }
 
 pub fn parse_cigar(cg: &str) -> eyre::Result<Vec<CigarOp>> {
-    let cg_iter = cg
+    let tokens: Vec<(CigarToken, char)> = cg
         .trim_start_matches("cg:Z:")
         .chars()
-        .chunk_by(|e| CigarToken::try_from(*e).unwrap());
+        .map(|e| Ok((CigarToken::try_from(e)?, e)))
+        .collect::<eyre::Result<_>>()?;
+    let cg_iter = tokens.into_iter().chunk_by(|(tk, _)| *tk);
     let mut cg_iter = cg_iter.into_iter().peekable();
     let mut cigar_ops = vec![];
 
     while let (Some((tk, mut elems)), Some((ntk, nelems))) = (cg_iter.next(), cg_iter.next()) {
-        let num = elems.join("").parse::<u32>()?;
+        let num = elems.by_ref().map(|(_, e)| e).join("").parse::<u32>()?;
         let cg_op = match (&tk, &ntk) {
             (CigarToken::Number, CigarToken::Match) => CigarOp::Match(num),
             (CigarToken::Number, CigarToken::Mismatch) => CigarOp::Mismatch(num),
@@ -72,8 +76,8 @@ pub fn parse_cigar(cg: &str) -> eyre::Result<Vec<CigarOp>> {
             (CigarToken::Number, CigarToken::Skip) => CigarOp::Skip(num),
             _ => bail!(
                 "Invalid cigar op ({tk:?}{:?}, {ntk:?}{:?})",
-                elems.collect_vec(),
-                nelems.collect_vec()
+                elems.map(|(_, e)| e).collect_vec(),
+                nelems.map(|(_, e)| e).collect_vec()
             ),
         };
         cigar_ops.push(cg_op);
@@ -81,6 +85,169 @@ pub fn parse_cigar(cg: &str) -> eyre::Result<Vec<CigarOp>> {
     Ok(cigar_ops)
 }
 
+/// Parse the cigar string of a PAF record with its query and target names on error.
+pub fn parse_record_cigar(rec: &PafRecord, cg: &str) -> eyre::Result<Vec<CigarOp>> {
+    parse_cigar(cg).with_context(|| {
+        format!(
+            "Invalid cigar for record ({} to {}).",
+            rec.query_name(),
+            rec.target_name()
+        )
+    })
+}
+
+/// Counts of aligned bases and gaps from a cigar string.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AlignmentStats {
+    pub matches: u64,
+    pub mismatches: u64,
+    pub ins_bp: u64,
+    pub ins_events: u64,
+    pub del_bp: u64,
+    pub del_events: u64,
+}
+
+impl AlignmentStats {
+    pub fn new(cg_ops: &[CigarOp]) -> Self {
+        let mut stats = AlignmentStats::default();
+        for cg_op in cg_ops {
+            match cg_op {
+                CigarOp::Match(l) => stats.matches += *l as u64,
+                CigarOp::Mismatch(l) => stats.mismatches += *l as u64,
+                CigarOp::Insertion(l) => {
+                    stats.ins_bp += *l as u64;
+                    stats.ins_events += 1;
+                }
+                CigarOp::Deletion(l) => {
+                    stats.del_bp += *l as u64;
+                    stats.del_events += 1;
+                }
+                CigarOp::Softclip(_)
+                | CigarOp::Hardclip(_)
+                | CigarOp::Pad(_)
+                | CigarOp::Skip(_) => {}
+            }
+        }
+        stats
+    }
+
+    /// Counts of aligned bases and gaps within a target interval of an alignment starting at `target_start`.
+    /// * Insertions are counted if they fall between bases of the interval.
+    pub fn from_target_window(
+        cg_ops: &[CigarOp],
+        target_start: u32,
+        start: u32,
+        stop: u32,
+    ) -> Self {
+        let mut stats = AlignmentStats::default();
+        let mut pos = target_start;
+        for cg_op in cg_ops {
+            if pos >= stop {
+                break;
+            }
+            let t_adj = match cg_op {
+                CigarOp::Match(l)
+                | CigarOp::Mismatch(l)
+                | CigarOp::Deletion(l)
+                | CigarOp::Skip(l) => *l,
+                _ => 0,
+            };
+            let ovl = (pos + t_adj).min(stop).saturating_sub(pos.max(start)) as u64;
+            match cg_op {
+                CigarOp::Match(_) => stats.matches += ovl,
+                CigarOp::Mismatch(_) => stats.mismatches += ovl,
+                CigarOp::Deletion(_) if ovl != 0 => {
+                    stats.del_bp += ovl;
+                    stats.del_events += 1;
+                }
+                CigarOp::Insertion(l) if pos > start => {
+                    stats.ins_bp += *l as u64;
+                    stats.ins_events += 1;
+                }
+                _ => {}
+            }
+            pos += t_adj;
+        }
+        stats
+    }
+
+    pub fn indels(&self) -> u64 {
+        self.ins_events + self.del_events
+    }
+
+    /// Identity with each gap counted as a single difference regardless of length. Same as `minimap2`'s `de` tag.
+    pub fn gap_compressed_identity(&self) -> f64 {
+        ratio(
+            self.matches,
+            self.matches + self.mismatches + self.ins_events + self.del_events,
+        )
+    }
+
+    /// Identity over all alignment columns with each gapped base counted as a difference.
+    pub fn blast_identity(&self) -> f64 {
+        ratio(
+            self.matches,
+            self.matches + self.mismatches + self.ins_bp + self.del_bp,
+        )
+    }
+
+    /// Identity over aligned bases only. Gaps are ignored.
+    pub fn identity(&self) -> f64 {
+        ratio(self.matches, self.matches + self.mismatches)
+    }
+
+    pub fn divergence(&self, metric: DivergenceMetric) -> f64 {
+        1.0 - match metric {
+            DivergenceMetric::GapCompressed => self.gap_compressed_identity(),
+            DivergenceMetric::Blast => self.blast_identity(),
+            DivergenceMetric::Identity => self.identity(),
+        }
+    }
+}
+
+fn ratio(num: u64, denom: u64) -> f64 {
+    if denom == 0 {
+        0.0
+    } else {
+        num as f64 / denom as f64
+    }
+}
+
+/// Sequence divergence of an alignment.
+/// * Gap-compressed divergence uses the `de` tag if present.
+/// * Otherwise, divergence is computed from the `cg` extended cigar string.
+pub fn get_seq_divergence(rec: &PafRecord, metric: DivergenceMetric) -> eyre::Result<f64> {
+    if let (DivergenceMetric::GapCompressed, Some(de)) = (metric, rec.de()) {
+        return Ok(*de);
+    }
+    let cg = rec
+        .cg()
+        .context("Record has no cigar or de tag to compute divergence.")?;
+    Ok(AlignmentStats::new(&parse_record_cigar(rec, cg)?).divergence(metric))
+}
+
+/// Offset of a target position from the query start of an alignment starting at `target_start`.
+/// * Positions within deletions map to the query base after the deletion.
+/// * Insertions are assigned to the target position before them.
+pub fn get_query_offset(cg_ops: &[CigarOp], target_start: u32, target_pos: u32) -> u32 {
+    let mut pos = target_start;
+    let mut qoffset = 0;
+    for cg_op in cg_ops {
+        let (q_adj, t_adj) = match cg_op {
+            CigarOp::Match(l) | CigarOp::Mismatch(l) => (*l, *l),
+            CigarOp::Insertion(l) | CigarOp::Softclip(l) | CigarOp::Pad(l) => (*l, 0),
+            CigarOp::Deletion(l) | CigarOp::Skip(l) => (0, *l),
+            CigarOp::Hardclip(_) => continue,
+        };
+        if target_pos < pos + t_adj {
+            return qoffset + if q_adj != 0 { target_pos - pos } else { 0 };
+        }
+        qoffset += q_adj;
+        pos += t_adj;
+    }
+    qoffset
+}
+
 /// Get intervals from query that align to target that meet some minimum length.
 pub fn get_aligned_paired_itvs(
     rec: &PafRecord,
@@ -89,7 +256,7 @@ pub fn get_aligned_paired_itvs(
     let mut pos: u32 = rec.target_start();
     let mut qpos: u32 = rec.query_start();
     let cg = rec.cg().context("Record has no cigar.")?;
-    let cg_ops = parse_cigar(cg)?;
+    let cg_ops = parse_record_cigar(rec, cg)?;
 
     let mut paired_itvs = vec![];
     for cg_op in cg_ops {
@@ -122,3 +289,41 @@ pub fn get_aligned_paired_itvs(
 
     Ok(paired_itvs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cigar() {
+        assert_eq!(
+            parse_cigar("cg:Z:10=1X2I3D").unwrap(),
+            [
+                CigarOp::Match(10),
+                CigarOp::Mismatch(1),
+                CigarOp::Insertion(2),
+                CigarOp::Deletion(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_cigar_invalid() {
+        assert!(parse_cigar("10M").is_err());
+        assert!(parse_cigar("10=2Y").is_err());
+    }
+
+    #[test]
+    fn test_get_query_offset() {
+        let cg_ops = parse_cigar("5=2I5=3D7=").unwrap();
+        assert_eq!(get_query_offset(&cg_ops, 10, 10), 0);
+        assert_eq!(get_query_offset(&cg_ops, 10, 13), 3);
+        // Insertions are assigned to the target position before them.
+        assert_eq!(get_query_offset(&cg_ops, 10, 15), 7);
+        // Positions within deletions map to the query base after the deletion.
+        assert_eq!(get_query_offset(&cg_ops, 10, 21), 12);
+        assert_eq!(get_query_offset(&cg_ops, 10, 22), 12);
+        assert_eq!(get_query_offset(&cg_ops, 10, 25), 14);
+        assert_eq!(get_query_offset(&cg_ops, 10, 30), 19);
+    }
+}
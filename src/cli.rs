@@ -1,6 +1,8 @@
 use std::{f32, path::PathBuf};
 
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+
+use crate::period::PeriodSpec;
 
 /// Script to take `srf` and `trf` output and produce a bed file with only regions corresponding monomers of a given periodicity.
 #[derive(Debug, Parser)]
@@ -8,6 +10,20 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Command,
+    /// TOML or YAML configuration file with options shared across subcommands.
+    /// Options given on the command-line take precedence.
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Output JSON report with version, parameters, input files, record counts, base pairs per period class, and runtime.
+    #[arg(long, global = true)]
+    pub report: Option<PathBuf>,
+    /// Increase logging verbosity. `-v` for debug and `-vv` for trace messages.
+    /// Overridden by `RUST_LOG`.
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+    /// Decrease logging verbosity. `-q` for only warnings and `-qq` for only errors.
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub quiet: u8,
 }
 
 #[derive(Debug, Subcommand)]
@@ -19,22 +35,83 @@ pub enum Command {
         paf: PathBuf,
         /// `trf` monomers TSV file on `srf` monomers with columns:
         /// `chrom (query), motif (target), st, end, period, copyNum, fracMatch, fracGap, score, entropy, pattern`
+        #[arg(short, long, required_unless_present = "fa")]
+        monomers: Option<PathBuf>,
+        /// Fasta file of `srf` motifs used to estimate monomers without `trf`.
+        /// Only used without `--monomers`. Motif lengths are taken from the sequences.
         #[arg(short, long)]
-        monomers: PathBuf,
-        /// Output BED9 file with columns:
-        /// `chrom, st, end, comma-delimited_monomers, 0, strand, st, end, '0,0,0'`
+        fa: Option<PathBuf>,
+        /// Minimum fraction of bases matching the next copy for estimated monomers.
+        /// Only used without `--monomers`.
+        #[arg(long, default_value_t = 0.7)]
+        min_identity: f32,
+        /// Output BED9+1 file with columns:
+        /// `chrom, st, end, comma-delimited_monomers, 0, strand, st, end, '0,0,0', period_class`
+        /// Period classes are labeled by family and harmonic. ex. `alpha_170` or `171x2`
         #[arg(short, long)]
         outfile: Option<PathBuf>,
-        /// Monomer size in base pairs to search for.
-        #[arg(short, long, default_values_t = [170, 340, 510, 680, 850, 1020, 42], num_args = 1..)]
-        sizes: Vec<u32>,
-        /// Percent difference in monomer period length allowed.
-        /// ex. `0.02` results in valid periods for `170`: `167 < 170 < 173`
-        #[arg(short, long, default_value_t = 0.02)]
-        diff: f32,
-        /// Maximum gap-compressed sequence divergence between aligned motif and region.
-        #[arg(short, long, default_value_t = 0.2)]
+        #[command(flatten)]
+        period: PeriodArgs,
+        /// Maximum sequence divergence between aligned motif and region.
+        #[arg(long, default_value_t = 0.2)]
         max_seq_div: f64,
+        /// Maximum percent difference between the aligned block and motif lengths for an alignment to be reported as a whole motif.
+        /// Independent of `--diff` and `--preset`.
+        #[arg(long, default_value_t = 0.02)]
+        whole_motif_diff: f32,
+        /// Minimum mapping quality of alignments.
+        #[arg(long, default_value_t = 0)]
+        min_mapq: u8,
+        /// Only use primary alignments. Removes alignments with `tp:A:S`.
+        #[arg(long, action)]
+        primary_only: bool,
+        /// Minimum alignment block length in base pairs.
+        #[arg(long, default_value_t = 0)]
+        min_aln_len: u32,
+        /// Minimum fraction of the enlonged motif covered by the alignment.
+        #[arg(long, default_value_t = 0.0)]
+        min_motif_coverage: f32,
+        /// Divergence metric used by `--max-seq-div`.
+        /// Computed from the `cg` extended cigar string if the PAF has no `de` tag.
+        #[arg(long, value_enum, default_value_t = DivergenceMetric::GapCompressed)]
+        div_metric: DivergenceMetric,
+        /// Add local alignment identity columns to each row:
+        /// `matches, mismatches, indels, identity`
+        /// Counted over the `trf` monomer footprint on the aligned block or over `--local-window`. `identity` is gap-compressed.
+        /// Rows of whole motif alignments are counted over the whole alignment.
+        #[arg(long, action)]
+        local_identity: bool,
+        /// Count local identity over this many base pairs on each side of the aligned block instead of the monomer footprint.
+        #[arg(long)]
+        local_window: Option<u32>,
+        /// Minimum gap-compressed local identity of each row.
+        #[arg(long, default_value_t = 0.0)]
+        min_local_identity: f64,
+        /// Resolve overlapping alignments of different motifs by keeping the best scoring alignment per base.
+        /// Rows are trimmed to the bases their alignment wins and sorted by position.
+        /// Adds a column of other motifs over each row. ex. `tie:circ2-1706:17926,lost:circ5-340:9000`
+        #[arg(long, value_enum)]
+        resolve_overlaps: Option<OverlapScore>,
+        /// Output TSV file of discarded PAF records and aligned intervals with columns:
+        /// `chrom, st, end, motif, reason, values`
+        /// A tally of each reason is printed at the end of the run regardless.
+        #[arg(long)]
+        rejected: Option<PathBuf>,
+        /// Tile aligned blocks into individual monomer copies using the `trf` period and phase on the `srf` motif.
+        /// Adds columns after the period class:
+        /// `copy_idx, period, is_partial`
+        /// The period class of each copy is from its `trf` period.
+        /// Copies of whole motif alignments are projected onto the query through the cigar.
+        #[arg(long, action)]
+        tile: bool,
+        /// `.fai` index of `srf` motifs used to get original motif lengths.
+        /// By default, lengths are parsed from motif names. ex. `circ2-1706`
+        /// Target positions are reduced modulo the motif length to handle enlonged and circular motifs.
+        #[arg(long)]
+        motif_fai: Option<PathBuf>,
+        /// Order of monomers in the name column.
+        #[arg(long, value_enum, default_value_t = MonomerOrder::First)]
+        order: MonomerOrder,
     },
     Motifs {
         #[arg(short, long)]
@@ -42,42 +119,281 @@ pub enum Command {
         fa: PathBuf,
         /// `trf` monomers TSV file on `srf` monomers with columns:
         /// `chrom (query), motif (target), st, end, period, copyNum, fracMatch, fracGap, score, entropy, pattern`
+        /// If not provided, monomers are estimated from the motif sequences.
         #[arg(short, long)]
-        monomers: PathBuf,
+        monomers: Option<PathBuf>,
         /// Output fasta file filtered to only motifs composed of monomers of given size.
         #[arg(short, long)]
         outfile: Option<PathBuf>,
-        /// Monomer size in base pairs to search for.
-        #[arg(short, long, default_values_t = [170, 340, 510, 680, 850, 1020], num_args = 1..)]
-        sizes: Vec<u32>,
-        /// Percent difference in monomer period length allowed.
-        /// ex. `0.02` results in valid periods for `170`: `167 < 170 < 173`
-        #[arg(short, long, default_value_t = 0.02)]
-        diff: f32,
+        #[command(flatten)]
+        period: PeriodArgs,
         /// Require all monomers to be within size range.
         #[arg(long, action)]
         require_all: bool,
+        /// Minimum fraction of bases matching the next copy for estimated monomers.
+        /// Only used without `--monomers`.
+        #[arg(long, default_value_t = 0.7)]
+        min_identity: f32,
+    },
+    Enlong {
+        /// Fasta file of srf detected motifs.
+        #[arg(short, long)]
+        fa: PathBuf,
+        /// Output fasta file of enlonged motifs to use as PAF targets for `monomers`.
+        /// Motif names are kept. ex. `prefix#circ2-1706`
+        #[arg(short, long)]
+        outfile: Option<PathBuf>,
+        /// Minimum length in base pairs of enlonged motifs.
+        /// Motifs are concatenated with themselves until at least this length.
+        #[arg(short, long, default_value_t = 200)]
+        min_len: u32,
+        /// Output `.fai`-like TSV file of original motif lengths with columns:
+        /// `motif, length`
+        /// Use with `monomers --motif-fai` if motif names don't contain their length.
+        #[arg(long)]
+        lengths_outfile: Option<PathBuf>,
     },
+    #[command(mut_arg("diff", |arg| arg.short(None)))]
     Regions {
-        /// Bed file from extract command.
+        /// BED3 to BED12 file from `monomers` command or other satellite annotators.
+        /// Comma-delimited monomers are read from the name column, if any.
+        /// Empty, `#`, `track`, and `browser` lines are skipped.
         #[arg(short, long)]
         bed: PathBuf,
-        /// Output BED9 file with columns:
-        /// `chrom, st, end, comma-delimited_monomers, 0, strand, st, end, '0,0,0'`
+        /// Output BED9+1 file with columns:
+        /// `chrom, st, end, comma-delimited_monomers, 0, strand, st, end, '0,0,0', period_class`
+        /// Period classes are labeled by family and harmonic. ex. `alpha_170` or `171x2`
         #[arg(short, long)]
         outfile: Option<PathBuf>,
         /// Distance to merge in base pairs.
+        /// Ignored with `--min-density`.
         #[arg(short, long, default_value_t = 100_000)]
         dst: u32,
         /// Minimum length in base pairs.
         #[arg(short, long, default_value_t = 30_000)]
         min_len: u32,
-        /// Required monomers in merged blocks. Merges iff one of these monomer periods is in block.
-        /// Also filters out monomers not within this period.
-        #[arg(short, long, default_values_t = [170, 340, 510, 680, 850, 1020], num_args = 1..)]
-        sizes: Vec<u32>,
-        /// Difference in required monomer size.
-        #[arg(long, default_value_t = 0.02)]
+        #[command(flatten)]
+        period: PeriodArgs,
+        /// Merge each monomer period class separately.
+        /// Outputs one region track per period class with the period class in the name column.
+        #[arg(long, action)]
+        by_period: bool,
+        /// Merge by density of monomer hits instead of by distance.
+        /// Keeps segments where the fraction of bases covered by hits in a sliding window is at least this value.
+        /// Region edges are trimmed to the first and last supporting hit and the fraction covered is reported in the score column.
+        #[arg(long)]
+        min_density: Option<f32>,
+        /// Sliding window size in base pairs for `--min-density`.
+        #[arg(long, default_value_t = 10_000, requires = "min_density")]
+        window: u32,
+        /// Add summary columns after the period class with columns:
+        /// `n_hits, covered_bp, frac_covered, n_monomers, period:bp, strand:n_hits`
+        #[arg(long, action)]
+        summary: bool,
+        /// Order of monomers in the name column.
+        #[arg(long, value_enum, default_value_t = MonomerOrder::First)]
+        order: MonomerOrder,
+        /// Input is already sorted by chrom and start.
+        /// Intervals are streamed and only the currently open region is kept in memory.
+        /// Not applicable with `--min-density`, which buffers intervals per chrom.
+        #[arg(long, action)]
+        sorted: bool,
+        /// Don't require or filter monomers by period.
+        /// By default, merged blocks require a monomer within `--sizes` and other monomers are filtered out.
+        /// Allows merging BED files from other satellite annotators where the name column isn't a monomer sequence.
+        #[arg(long, action)]
+        no_monomer_filter: bool,
+        /// Minimum number of merged intervals in a region.
+        #[arg(long, default_value_t = 1)]
+        min_hits: usize,
+    },
+    Extract {
+        /// BED3 to BED12 file from `monomers` or `regions` command.
+        #[arg(short, long)]
+        bed: PathBuf,
+        /// Assembly fasta file indexed with `samtools faidx`.
+        #[arg(short, long)]
+        assembly: PathBuf,
+        /// `.fai` index of assembly. Defaults to `{assembly}.fai`.
+        #[arg(long)]
+        fai: Option<PathBuf>,
+        /// Output fasta file with headers:
+        /// `>chrom:st-end(strand) period=period monomers=monomer_ids`
+        #[arg(short, long)]
+        outfile: Option<PathBuf>,
+        /// Output fasta file of monomer IDs and their sequences.
+        #[arg(long)]
+        monomer_outfile: Option<PathBuf>,
+        #[command(flatten)]
+        period: PeriodArgs,
+        /// Reverse complement sequences on the `-` strand.
+        #[arg(long, action)]
+        revcomp: bool,
+    },
+    Stats {
+        /// Bed file from `monomers` or `regions` command.
+        #[arg(short, long)]
+        bed: PathBuf,
+        /// `.fai` index of assembly used for contig lengths.
+        /// Required for fraction of contig covered.
+        #[arg(short, long)]
+        fai: Option<PathBuf>,
+        /// Output summary with columns:
+        /// `chrom, period, bp, n_arrays, largest_array, n50, frac_covered`
+        /// Per-contig and per-period totals are labeled `all`.
+        #[arg(short, long)]
+        outfile: Option<PathBuf>,
+        #[command(flatten)]
+        period: PeriodArgs,
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Tsv)]
+        format: OutputFormat,
+    },
+    Periods {
+        /// PAF file of alignment of assembly as query and `srf` enlonged motifs as target.
+        #[arg(short, long)]
+        paf: PathBuf,
+        /// `trf` monomers TSV file on `srf` monomers with columns:
+        /// `chrom (query), motif (target), st, end, period, copyNum, fracMatch, fracGap, score, entropy, pattern`
+        #[arg(short, long)]
+        monomers: PathBuf,
+        /// Output spectrum of `trf` periods weighted by aligned base pairs with columns:
+        /// `period, bp, frac_bp, peak`
+        #[arg(short, long)]
+        outfile: Option<PathBuf>,
+        /// Output peaks with columns:
+        /// `period, min, max, bp, frac_bp, base, multiple`
+        #[arg(long)]
+        peaks_outfile: Option<PathBuf>,
+        /// Percent difference in period length of periods in a peak and of harmonics.
+        #[arg(short, long, default_value_t = 0.02)]
         diff: f32,
+        /// Minimum fraction of aligned base pairs in a peak.
+        #[arg(long, default_value_t = 0.01)]
+        min_frac: f32,
+        /// Largest multiple of a peak considered a harmonic.
+        #[arg(long, default_value_t = 6)]
+        max_multiple: u32,
+        /// `.fai` index of `srf` motifs used to get original motif lengths.
+        /// By default, lengths are parsed from motif names. ex. `circ2-1706`
+        #[arg(long)]
+        motif_fai: Option<PathBuf>,
     },
+    Abundance {
+        /// PAF file of alignment of assembly as query and `srf` enlonged motifs as target.
+        #[arg(short, long)]
+        paf: PathBuf,
+        /// `trf` monomers TSV file on `srf` monomers with columns:
+        /// `chrom (query), motif (target), st, end, period, copyNum, fracMatch, fracGap, score, entropy, pattern`
+        /// Used for the period composition of each motif.
+        #[arg(short, long)]
+        monomers: Option<PathBuf>,
+        /// Fasta file of `srf` motifs used to estimate monomers without `trf`.
+        /// Only used without `--monomers`. Motif lengths are taken from the sequences.
+        #[arg(short, long)]
+        fa: Option<PathBuf>,
+        /// Minimum fraction of bases matching the next copy for estimated monomers.
+        /// Only used without `--monomers`.
+        #[arg(long, default_value_t = 0.7)]
+        min_identity: f32,
+        /// Output TSV file ranked by aligned base pairs with columns:
+        /// `motif, motif_len, aligned_bp, n_alignments, mean_de, periods`
+        /// `periods` is the base pairs of the motif covered by monomers of each period. ex. `171:1304,342:1376`
+        #[arg(short, long)]
+        outfile: Option<PathBuf>,
+        /// `.fai` index of `srf` motifs used to get original motif lengths.
+        /// By default, lengths are parsed from motif names. ex. `circ2-1706`
+        #[arg(long)]
+        motif_fai: Option<PathBuf>,
+        /// Report abundance per assembly contig and motif. Adds a `chrom` column.
+        #[arg(long, action)]
+        by_chrom: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Tsv,
+    Json,
+}
+
+/// Monomer periods used to search for, filter, and label monomers by period class.
+#[derive(Debug, Clone, Args)]
+pub struct PeriodArgs {
+    /// Monomer size in base pairs.
+    /// Optionally with a difference or range per size. ex. `170:0.02`, `42:3bp`, or `171-172`
+    /// Prefix with a family to label its period class. ex. `alpha=170`
+    #[arg(short, long, default_values = ["170", "340", "510", "680", "850", "1020", "42"], num_args = 1..)]
+    pub sizes: Vec<PeriodSpec>,
+    /// Percent difference in monomer period length allowed.
+    /// ex. `0.02` results in valid periods for `170`: `167 <= 170 <= 173`
+    /// Used by sizes without their own difference.
+    #[arg(short, long, default_value_t = 0.02)]
+    pub diff: f32,
+    /// Satellite family preset with named monomer periods and per-family differences.
+    /// Replaces `--sizes` and `--diff`. Period classes are labeled by family. ex. `alpha_340`
+    #[arg(long, value_enum, conflicts_with_all = ["sizes", "diff"])]
+    pub preset: Option<Preset>,
+    /// Also match integer multiples of each size up to this number.
+    /// ex. `3` with `171` matches `171`, `342`, and `513`
+    /// Period classes are labeled by base unit and multiple. ex. `171x2`
+    #[arg(long, default_value_t = 1)]
+    pub harmonics: u32,
+}
+
+/// Order of monomers in the name column.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MonomerOrder {
+    /// Order of first occurrence.
+    #[default]
+    First,
+    /// Most to least abundant with counts. ex. `seq:12`
+    Abundance,
+    /// Lexicographic order.
+    Lexicographic,
+}
+
+/// Sequence divergence metric. Each is one minus an identity.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DivergenceMetric {
+    /// Gaps count as a single difference. `1 - matches / (matches + mismatches + gap_opens)`
+    /// Same as `minimap2`'s `de` tag.
+    #[default]
+    GapCompressed,
+    /// Gapped bases each count as a difference. `1 - matches / (matches + mismatches + gap_bp)`
+    Blast,
+    /// Gaps are ignored. `1 - matches / (matches + mismatches)`
+    Identity,
+}
+
+/// Alignment score used to resolve overlaps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OverlapScore {
+    /// Alignment score from the `AS` tag.
+    As,
+    /// One minus the divergence from `--div-metric`.
+    Identity,
+    /// Fraction of the enlonged motif covered by the alignment.
+    Coverage,
+}
+
+/// Satellite family preset. See [`crate::period::SATELLITE_FAMILIES`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// α-satellite. `170, 340, 510, 680, 850, 1020` ±2%
+    Alpha,
+    /// HSat1A. `42` ±5%
+    Hsat1a,
+    /// HSat1B. `2500` ±5%
+    Hsat1b,
+    /// HSat2. `23, 26` ±5%
+    Hsat2,
+    /// HSat3. `5`
+    Hsat3,
+    /// β-satellite. `68` ±3%
+    Beta,
+    /// γ-satellite. `220` ±3%
+    Gamma,
+    /// All satellite families.
+    All,
 }
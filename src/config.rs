@@ -0,0 +1,269 @@
+use std::{collections::BTreeMap, ffi::OsString, path::Path};
+
+use clap::{Arg, ArgAction, ArgMatches, Command, parser::ValueSource};
+use eyre::{ContextCompat, bail};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Satellite family in a configuration file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FamilyConfig {
+    pub name: String,
+    /// Period specifications. ex. `170`, `42:3bp`, or `171-172`
+    pub sizes: Vec<Value>,
+    /// Percent difference in period length for sizes without their own difference.
+    pub diff: Option<f32>,
+}
+
+/// Run configuration shared across subcommands.
+/// * `families` are added to `--sizes` labeled by family.
+/// * Other top-level options apply to every subcommand that has them.
+/// * Tables named after a subcommand only apply to that subcommand. ex. `[regions]`
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub families: Vec<FamilyConfig>,
+    #[serde(flatten)]
+    pub options: BTreeMap<String, Value>,
+}
+
+impl Config {
+    /// Read a TOML or YAML configuration file. The format is inferred from the extension.
+    pub fn from_path(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(toml::from_str(&contents)?),
+            Some("yaml" | "yml") => Ok(serde_yaml::from_str(&contents)?),
+            _ => bail!("Unknown config format ({path:?}). Must be .toml, .yaml, or .yml."),
+        }
+    }
+
+    /// Options for a subcommand with subcommand-specific options taking precedence.
+    /// * Families are converted to `--sizes` values. ex. `alpha=170:0.02`
+    fn subcommand_options(&self, subcommand: &str) -> eyre::Result<BTreeMap<String, Value>> {
+        let mut options: BTreeMap<String, Value> = BTreeMap::new();
+        let mut sizes = vec![];
+        for family in self.families.iter() {
+            for size in family.sizes.iter() {
+                let size = value_to_string(size)
+                    .with_context(|| format!("Invalid size in family ({}).", family.name))?;
+                let has_diff = size.contains([':', '-']);
+                sizes.push(Value::String(match family.diff {
+                    Some(diff) if !has_diff => format!("{}={size}:{diff}", family.name),
+                    _ => format!("{}={size}", family.name),
+                }));
+            }
+        }
+        for (key, value) in self.options.iter() {
+            match value {
+                Value::Object(_) => continue,
+                // Shared sizes are added to families.
+                Value::Array(values) if normalize_key(key) == "sizes" => {
+                    sizes.extend(values.iter().cloned())
+                }
+                _ => {
+                    options.insert(normalize_key(key), value.clone());
+                }
+            }
+        }
+        if !sizes.is_empty() {
+            options.insert(String::from("sizes"), Value::Array(sizes));
+        }
+        if let Some(Value::Object(subcommand_options)) = self.options.get(subcommand) {
+            for (key, value) in subcommand_options {
+                options.insert(normalize_key(key), value.clone());
+            }
+        }
+        Ok(options)
+    }
+
+    /// Check that every top-level option is accepted by a subcommand or is a global option.
+    /// * Tables must be named after a subcommand.
+    fn validate(&self, cmd: &Command) -> eyre::Result<()> {
+        for (key, value) in self.options.iter() {
+            let key = normalize_key(key);
+            let is_known = if let Value::Object(_) = value {
+                cmd.find_subcommand(&key).is_some()
+            } else {
+                global_args(cmd).any(|arg| arg.get_id() == key.as_str())
+                    || cmd
+                        .get_subcommands()
+                        .flat_map(|sub_cmd| sub_cmd.get_arguments())
+                        .any(|arg| arg.get_id() == key.as_str())
+            };
+            if !is_known {
+                bail!(
+                    "Unknown option ({key}) in config. No subcommand or global option accepts it."
+                )
+            }
+        }
+        Ok(())
+    }
+
+    /// Get arguments for options not given on the command-line.
+    /// * Options that conflict with an option given on the command-line are skipped.
+    /// * Shared options are skipped if a subcommand doesn't have them.
+    /// * Global options apply to every subcommand. ex. `report` or `verbose = 2`
+    pub fn to_args(&self, cmd: &Command, matches: &ArgMatches) -> eyre::Result<Vec<OsString>> {
+        let Some((subcommand, sub_matches)) = matches.subcommand() else {
+            return Ok(vec![]);
+        };
+        self.validate(cmd)?;
+        let sub_cmd = cmd
+            .find_subcommand(subcommand)
+            .with_context(|| format!("Unknown subcommand ({subcommand})."))?;
+        // Global options are only propagated to subcommands once the command is built.
+        let is_cli_arg = |id: &str| {
+            [matches, sub_matches].into_iter().any(|matches| {
+                matches.try_contains_id(id).unwrap_or_default()
+                    && matches.value_source(id) == Some(ValueSource::CommandLine)
+            })
+        };
+        let subcommand_keys: Vec<String> = match self.options.get(subcommand) {
+            Some(Value::Object(options)) => options.keys().map(|key| normalize_key(key)).collect(),
+            _ => vec![],
+        };
+
+        let mut args = vec![];
+        for (key, value) in self.subcommand_options(subcommand)? {
+            let Some(arg) = sub_cmd
+                .get_arguments()
+                .chain(global_args(cmd))
+                .find(|arg| arg.get_id() == key.as_str())
+            else {
+                if subcommand_keys.contains(&key) {
+                    bail!("Unknown option ({key}) for {subcommand} in config.")
+                }
+                continue;
+            };
+            let Some(long) = arg.get_long() else {
+                continue;
+            };
+            let is_conflicting = sub_cmd.get_arguments().any(|other| {
+                is_cli_arg(other.get_id().as_str())
+                    && (sub_cmd.get_arg_conflicts_with(arg).contains(&other)
+                        || sub_cmd.get_arg_conflicts_with(other).contains(&arg))
+            });
+            if is_cli_arg(&key) || is_conflicting {
+                continue;
+            }
+            let takes_values = arg.get_action().takes_values();
+            match value {
+                Value::Bool(flag) if !takes_values => {
+                    if flag {
+                        args.push(OsString::from(format!("--{long}")));
+                    }
+                }
+                // Repeat counted flags. ex. `verbose = 2` -> `--verbose --verbose`
+                Value::Number(count) if matches!(arg.get_action(), ArgAction::Count) => {
+                    let count = count
+                        .as_u64()
+                        .with_context(|| format!("Invalid count for {key} in config."))?;
+                    args.extend((0..count).map(|_| OsString::from(format!("--{long}"))));
+                }
+                Value::Array(values) => {
+                    for value in values {
+                        let value = value_to_string(&value)
+                            .with_context(|| format!("Invalid value for {key} in config."))?;
+                        args.push(OsString::from(format!("--{long}={value}")));
+                    }
+                }
+                value => {
+                    let value = value_to_string(&value)
+                        .with_context(|| format!("Invalid value for {key} in config."))?;
+                    args.push(OsString::from(format!("--{long}={value}")));
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Global options that can be set in a config. `config` itself can't be nested.
+fn global_args(cmd: &Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments()
+        .filter(|arg| arg.is_global_set() && arg.get_id() != "config")
+}
+
+/// Option names can be snake or kebab case. ex. `max-seq-div` -> `max_seq_div`
+fn normalize_key(key: &str) -> String {
+    key.replace('-', "_")
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+    use crate::cli::Cli;
+
+    fn to_args(config: &str, args: &[&str]) -> eyre::Result<Vec<String>> {
+        let config: Config = toml::from_str(config)?;
+        let cmd = Cli::command();
+        let matches = cmd.clone().try_get_matches_from(args)?;
+        Ok(config
+            .to_args(&cmd, &matches)?
+            .into_iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    #[test]
+    fn test_to_args_unknown_key() {
+        let err = to_args("max_seq_dv = 0.1", &["srf-n-trf", "regions", "-b", "a.bed"]);
+        assert!(err.unwrap_err().to_string().contains("max_seq_dv"));
+    }
+
+    #[test]
+    fn test_to_args_unknown_table() {
+        let err = to_args(
+            "[region]\nmin-len = 1",
+            &["srf-n-trf", "regions", "-b", "a.bed"],
+        );
+        assert!(err.unwrap_err().to_string().contains("region"));
+    }
+
+    #[test]
+    fn test_to_args_shared_and_global() {
+        // max-seq-div is only a monomers option.
+        let args = to_args(
+            "max-seq-div = 0.1\nreport = \"run.json\"\nverbose = 2\n[regions]\nmin-len = 5",
+            &["srf-n-trf", "regions", "-b", "a.bed"],
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            ["--min-len=5", "--report=run.json", "--verbose", "--verbose"]
+        );
+    }
+
+    #[test]
+    fn test_to_args_command_line_precedence() {
+        let args = to_args(
+            "report = \"run.json\"\nquiet = 1\n[regions]\nmin-len = 5",
+            &[
+                "srf-n-trf",
+                "regions",
+                "-b",
+                "a.bed",
+                "--min-len",
+                "10",
+                "--report",
+                "cli.json",
+            ],
+        )
+        .unwrap();
+        assert_eq!(args, ["--quiet"]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use eyre::{Context, ContextCompat, bail};
+use itertools::Itertools;
+
+/// Record in a `samtools faidx` index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaiRecord {
+    pub length: u64,
+    pub offset: u64,
+    pub line_bases: u64,
+    pub line_width: u64,
+}
+
+/// FASTA file with a `samtools faidx` index.
+#[derive(Debug)]
+pub struct IndexedFasta {
+    reader: BufReader<File>,
+    index: HashMap<String, FaiRecord>,
+}
+
+impl IndexedFasta {
+    /// Open an indexed FASTA file. Uses `{fa}.fai` if no index is provided.
+    pub fn from_path(fa: impl AsRef<Path>, fai: Option<impl AsRef<Path>>) -> eyre::Result<Self> {
+        let fa = fa.as_ref();
+        let fai = fai.map(|fai| fai.as_ref().to_owned()).unwrap_or_else(|| {
+            let mut fai = OsString::from(fa);
+            fai.push(".fai");
+            PathBuf::from(fai)
+        });
+        let fai_reader = BufReader::new(
+            File::open(&fai).with_context(|| format!("Cannot open index ({fai:?})."))?,
+        );
+        let mut index = HashMap::new();
+        for line in fai_reader.lines() {
+            let line = line?;
+            let Some((name, length, offset, line_bases, line_width)) =
+                line.split('\t').take(5).collect_tuple()
+            else {
+                bail!("Invalid index line ({line}).")
+            };
+            index.insert(
+                name.to_owned(),
+                FaiRecord {
+                    length: length.parse()?,
+                    offset: offset.parse()?,
+                    line_bases: line_bases.parse()?,
+                    line_width: line_width.parse()?,
+                },
+            );
+        }
+        Ok(IndexedFasta {
+            reader: BufReader::new(File::open(fa)?),
+            index,
+        })
+    }
+
+    /// Fetch the sequence of `[start, end)` on `chrom`.
+    /// * Errors if the interval ends past the end of `chrom`.
+    pub fn fetch(&mut self, chrom: &str, start: u32, end: u32) -> eyre::Result<Vec<u8>> {
+        let rec = *self
+            .index
+            .get(chrom)
+            .with_context(|| format!("{chrom} not in index."))?;
+        let (start, end) = (start as u64, end as u64);
+        if end > rec.length {
+            bail!(
+                "Interval ({chrom}:{start}-{end}) ends past the end of {chrom} ({}).",
+                rec.length
+            )
+        }
+        if start >= end {
+            return Ok(vec![]);
+        }
+        let file_pos =
+            |pos: u64| rec.offset + (pos / rec.line_bases) * rec.line_width + pos % rec.line_bases;
+        let (file_start, file_end) = (file_pos(start), file_pos(end - 1) + 1);
+
+        let mut buf = vec![0; (file_end - file_start) as usize];
+        self.reader.seek(SeekFrom::Start(file_start))?;
+        self.reader.read_exact(&mut buf)?;
+        buf.retain(|b| *b != b'\n' && *b != b'\r');
+        Ok(buf)
+    }
+}
+
+/// Reverse complement a nucleotide sequence. Keeps case and leaves unknown bases as-is.
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|b| match b {
+            b'A' => b'T',
+            b'T' => b'A',
+            b'G' => b'C',
+            b'C' => b'G',
+            b'a' => b't',
+            b't' => b'a',
+            b'g' => b'c',
+            b'c' => b'g',
+            _ => *b,
+        })
+        .collect()
+}
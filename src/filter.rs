@@ -0,0 +1,132 @@
+use std::fmt::Display;
+
+use paf::PafRecord;
+
+/// Reason a PAF record was removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FilterReason {
+    Mapq,
+    Secondary,
+    AlnLen,
+    MotifCoverage,
+}
+
+impl FilterReason {
+    pub const ALL: [FilterReason; 4] = [
+        FilterReason::Mapq,
+        FilterReason::Secondary,
+        FilterReason::AlnLen,
+        FilterReason::MotifCoverage,
+    ];
+
+    /// Reason code in the rejection report.
+    pub fn code(&self) -> &'static str {
+        match self {
+            FilterReason::Mapq => "low_mapq",
+            FilterReason::Secondary => "secondary",
+            FilterReason::AlnLen => "short_alignment",
+            FilterReason::MotifCoverage => "low_motif_coverage",
+        }
+    }
+
+    /// Values of a record involved in this reason. ex. `mapq=0`
+    pub fn values(&self, rec: &PafRecord) -> String {
+        match self {
+            FilterReason::Mapq => format!("mapq={}", rec.mapping_quality()),
+            FilterReason::Secondary => String::from("tp=S"),
+            FilterReason::AlnLen => format!("aln_len={}", rec.alignment_block_len()),
+            FilterReason::MotifCoverage => {
+                format!("motif_coverage={:.6}", AlignmentFilter::motif_coverage(rec))
+            }
+        }
+    }
+
+    /// Option that removes records for this reason.
+    pub fn option(&self) -> &'static str {
+        match self {
+            FilterReason::Mapq => "min_mapq",
+            FilterReason::Secondary => "primary_only",
+            FilterReason::AlnLen => "min_aln_len",
+            FilterReason::MotifCoverage => "min_motif_coverage",
+        }
+    }
+}
+
+/// Alignment-level filters on PAF records with counts of removed records.
+#[derive(Debug, Default, Clone)]
+pub struct AlignmentFilter {
+    pub min_mapq: u8,
+    pub primary_only: bool,
+    pub min_aln_len: u32,
+    pub min_motif_coverage: f32,
+    n_total: usize,
+    n_removed: [usize; FilterReason::ALL.len()],
+}
+
+impl AlignmentFilter {
+    pub fn new(
+        min_mapq: u8,
+        primary_only: bool,
+        min_aln_len: u32,
+        min_motif_coverage: f32,
+    ) -> Self {
+        AlignmentFilter {
+            min_mapq,
+            primary_only,
+            min_aln_len,
+            min_motif_coverage,
+            ..Default::default()
+        }
+    }
+
+    /// Fraction of the target motif covered by the alignment.
+    pub fn motif_coverage(rec: &PafRecord) -> f32 {
+        (rec.target_end() - rec.target_start()) as f32 / rec.target_len() as f32
+    }
+
+    /// Check if a record passes all filters. Removed records are counted by the first filter they fail.
+    /// * Records without a `tp` tag are treated as primary.
+    pub fn check(&mut self, rec: &PafRecord) -> Result<(), FilterReason> {
+        self.n_total += 1;
+        let reason = if rec.mapping_quality() < self.min_mapq {
+            Some(FilterReason::Mapq)
+        } else if self.primary_only && rec.tp().is_some_and(|tp| *tp == 'S') {
+            Some(FilterReason::Secondary)
+        } else if rec.alignment_block_len() < self.min_aln_len {
+            Some(FilterReason::AlnLen)
+        } else if Self::motif_coverage(rec) < self.min_motif_coverage {
+            Some(FilterReason::MotifCoverage)
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => {
+                self.n_removed[reason as usize] += 1;
+                Err(reason)
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn n_removed(&self, reason: FilterReason) -> usize {
+        self.n_removed[reason as usize]
+    }
+
+    pub fn n_total(&self) -> usize {
+        self.n_total
+    }
+
+    pub fn n_kept(&self) -> usize {
+        self.n_total - self.n_removed.iter().sum::<usize>()
+    }
+}
+
+impl Display for AlignmentFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "total\t{}", self.n_total)?;
+        for reason in FilterReason::ALL {
+            writeln!(f, "{}\t{}", reason.option(), self.n_removed(reason))?;
+        }
+        write!(f, "kept\t{}", self.n_kept())
+    }
+}
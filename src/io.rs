@@ -5,6 +5,7 @@ use std::{
     path::Path,
 };
 
+use eyre::{Context, bail};
 use itertools::Itertools;
 use ordered_float::OrderedFloat;
 use rust_lapper::{Interval, Lapper};
@@ -17,7 +18,7 @@ pub struct Monomer {
     pub trf_copy_num: OrderedFloat<f32>,
 }
 
-type MotifMonomers = HashMap<String, Lapper<u32, Monomer>>;
+pub type MotifMonomers = HashMap<String, Lapper<u32, Monomer>>;
 
 /*
 INPUT_TRF_COLS = (
@@ -73,3 +74,173 @@ pub fn read_trf_monomers(infile: impl AsRef<Path>) -> eyre::Result<MotifMonomers
     }
     Ok(motif_monomers)
 }
+
+/// Read fasta records as names and sequences.
+/// * Names are the first word of the header.
+/// * Sequences may span multiple lines.
+pub fn read_fasta(reader: impl BufRead) -> eyre::Result<Vec<(String, Vec<u8>)>> {
+    let mut records: Vec<(String, Vec<u8>)> = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(header) = line.trim().strip_prefix('>') {
+            let name = header.split_whitespace().next().unwrap_or_default();
+            records.push((name.to_owned(), vec![]));
+        } else if let Some((_, seq)) = records.last_mut() {
+            seq.extend(line.trim().as_bytes());
+        } else if !line.trim().is_empty() {
+            bail!("Sequence before first fasta header ({line}).")
+        }
+    }
+    Ok(records)
+}
+
+/// Read sequence lengths from a `samtools faidx` index.
+pub fn read_fai(infile: impl AsRef<Path>) -> eyre::Result<HashMap<String, u32>> {
+    let reader = BufReader::new(File::open(infile)?);
+    let mut lengths = HashMap::new();
+    for line in reader.lines().map_while(Result::ok) {
+        let Some((name, length)) = line.split('\t').take(2).collect_tuple() else {
+            continue;
+        };
+        lengths.insert(name.to_owned(), length.parse()?);
+    }
+    Ok(lengths)
+}
+
+/// BED3+ record. Columns after the strand are ignored except the period class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BedRecord<'a> {
+    pub chrom: &'a str,
+    pub start: u32,
+    pub end: u32,
+    pub name: Option<&'a str>,
+    pub strand: Option<char>,
+    /// Period class column of `monomers` and `regions` output, if any. ex. `alpha_170`
+    pub period_class: Option<&'a str>,
+}
+
+/// Parse a BED3 to BED12 line.
+/// * Returns `None` for empty, `#` comment, `track`, and `browser` lines.
+/// * `.` in the name or strand column is treated as missing.
+pub fn parse_bed_line(line: &str, line_num: usize) -> eyre::Result<Option<BedRecord<'_>>> {
+    if line.trim().is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+    {
+        return Ok(None);
+    }
+    let mut cols = line.trim_end_matches(['\r', '\n']).split('\t');
+    let (Some(chrom), Some(st), Some(end)) = (cols.next(), cols.next(), cols.next()) else {
+        bail!("Expected at least 3 columns on line {line_num}. Got ({line}).")
+    };
+    let start: u32 = st
+        .parse()
+        .with_context(|| format!("Invalid start ({st}) on line {line_num}."))?;
+    let end: u32 = end
+        .parse()
+        .with_context(|| format!("Invalid end ({end}) on line {line_num}."))?;
+    if start > end {
+        bail!("Start ({start}) is greater than end ({end}) on line {line_num}.")
+    }
+    let name = cols.next().filter(|name| !name.is_empty() && *name != ".");
+    let strand = cols.nth(1).and_then(|strand| match strand {
+        "+" => Some('+'),
+        "-" => Some('-'),
+        _ => None,
+    });
+    // Skip thick start, thick end, and color.
+    let period_class = cols.nth(3).filter(|period_class| !period_class.is_empty());
+    Ok(Some(BedRecord {
+        chrom,
+        start,
+        end,
+        name,
+        strand,
+        period_class,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bed3() {
+        assert_eq!(
+            parse_bed_line("chr1\t0\t170\n", 1).unwrap(),
+            Some(BedRecord {
+                chrom: "chr1",
+                start: 0,
+                end: 170,
+                name: None,
+                strand: None,
+                period_class: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_bed6() {
+        assert_eq!(
+            parse_bed_line("chr1\t0\t170\tm0,m1\t0\t-", 1).unwrap(),
+            Some(BedRecord {
+                chrom: "chr1",
+                start: 0,
+                end: 170,
+                name: Some("m0,m1"),
+                strand: Some('-'),
+                period_class: None,
+            })
+        );
+        // `.` is missing.
+        let rec = parse_bed_line("chr1\t0\t170\t.\t0\t.", 1).unwrap().unwrap();
+        assert_eq!((rec.name, rec.strand), (None, None));
+    }
+
+    #[test]
+    fn test_parse_bed9_plus() {
+        let rec = parse_bed_line("chr1\t0\t170\tm0\t0\t+\t0\t170\t0,0,0", 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(rec.period_class, None);
+        let rec = parse_bed_line(
+            "chr1\t0\t340\talpha_170\t0\t+\t0\t340\t0,0,0\talpha_170\t0\t170",
+            1,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            (rec.name, rec.strand, rec.period_class),
+            (Some("alpha_170"), Some('+'), Some("alpha_170"))
+        );
+    }
+
+    #[test]
+    fn test_parse_bed_skipped_lines() {
+        for line in [
+            "",
+            "\n",
+            "# chrom\tst\tend",
+            "track name=regions",
+            "browser position chr1:1-100",
+        ] {
+            assert_eq!(parse_bed_line(line, 1).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_parse_bed_errors() {
+        let err = |line: &str| parse_bed_line(line, 7).unwrap_err().to_string();
+        assert_eq!(
+            err("chr1\t0"),
+            "Expected at least 3 columns on line 7. Got (chr1\t0)."
+        );
+        assert_eq!(err("chr1\ta\t170"), "Invalid start (a) on line 7.");
+        assert_eq!(err("chr1\t0\t-1"), "Invalid end (-1) on line 7.");
+        assert_eq!(
+            err("chr1\t170\t0"),
+            "Start (170) is greater than end (0) on line 7."
+        );
+    }
+}
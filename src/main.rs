@@ -1,42 +1,68 @@
 use std::{
-    collections::{HashSet, VecDeque},
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
     ffi::OsStr,
     fs::File,
     io::{BufRead, BufReader, BufWriter, Write, stdin, stdout},
+    path::PathBuf,
+    time::Instant,
 };
 
-use clap::Parser;
-use eyre::bail;
+use clap::{CommandFactory, FromArgMatches};
+use eyre::{Context, ContextCompat, bail};
 use itertools::Itertools;
-use paf::Reader;
+use log::{LevelFilter, debug, info};
+use paf::{PafRecord, Reader};
 use rust_lapper::{Interval, Lapper};
 
+mod abundance;
 mod cigar;
 mod cli;
+mod config;
+mod fasta;
+mod filter;
 mod io;
+mod monomer;
+mod motif;
+mod overlap;
+mod period;
+mod regions;
+mod reject;
+mod report;
+mod spectrum;
+mod stats;
+mod tandem;
+mod tile;
 
 use crate::{
-    cigar::get_aligned_paired_itvs,
-    cli::{Cli, Command},
-    io::read_trf_monomers,
+    abundance::{ABUNDANCE_TSV_HEADER, MotifAbundance, period_composition},
+    cigar::{AlignmentStats, get_aligned_paired_itvs, get_seq_divergence, parse_record_cigar},
+    cli::{Cli, Command, MonomerOrder, OutputFormat, PeriodArgs},
+    config::Config,
+    fasta::{IndexedFasta, reverse_complement},
+    filter::AlignmentFilter,
+    io::{Monomer, MotifMonomers, parse_bed_line, read_fai, read_fasta, read_trf_monomers},
+    monomer::{CopyColumns, MonomerIndex, MonomerRow, get_bed_period_class, get_name_period_class},
+    motif::{find_circular, get_motif_len},
+    overlap::{OverlapResolver, get_overlap_score},
+    period::{Harmonic, PeriodMatcher, get_period_specs},
+    regions::{
+        BedInterval, Region, RegionFilter, RegionMerger, SortedCheck, merge_intervals_by_density,
+        split_by_period,
+    },
+    reject::{RejectReason, Rejections},
+    report::{RecordCounts, RunReport},
+    spectrum::{PEAKS_TSV_HEADER, PeriodSpectrum, SPECTRUM_TSV_HEADER, period_spec},
+    stats::{ALL, STATS_TSV_HEADER, summarize_bed},
+    tandem::estimate_motif_monomers,
+    tile::{AlignedBlock, tile_monomer_copies},
 };
 
-pub fn create_monomer_range(sizes: &[u32], diff: f32) -> Lapper<u32, ()> {
-    Lapper::new(
-        sizes
-            .iter()
-            .map(|period| {
-                let allowed_diff = *period as f32 * diff;
-                let lower_bound = *period as f32 - allowed_diff;
-                let upper_bound = *period as f32 + allowed_diff;
-                Interval {
-                    start: lower_bound as u32,
-                    stop: upper_bound as u32 + 1,
-                    val: (),
-                }
-            })
-            .collect(),
-    )
+/// Label the period class of comma-delimited monomers or `.` if none. ex. `alpha_170` or `171x2`
+fn label_period_class(monomers: &str, period_matcher: &PeriodMatcher) -> String {
+    get_name_period_class(monomers, period_matcher)
+        .map(|period| period_matcher.label(period))
+        .unwrap_or_else(|| String::from("."))
 }
 
 /// `writeln!()` but handles broken pipes.
@@ -51,177 +77,667 @@ macro_rules! writeln_w_bp {
     };
 }
 
+/// Get one BED10+3 row per monomer copy with columns:
+/// `chrom, st, end, monomer, 0, strand, st, end, '0,0,0', period_class, copy_idx, period, is_partial`
+/// * The period class is from the `trf` period.
+fn get_monomer_copy_rows(
+    rows: &mut Vec<MonomerRow>,
+    rec: &PafRecord,
+    block: &AlignedBlock,
+    monomers: &[Interval<u32, &Monomer>],
+    local_cols: &str,
+    period_matcher: &PeriodMatcher,
+) {
+    for monomer in monomers {
+        let period_class = period_matcher
+            .period(monomer.val.trf_period)
+            .map(|period| period_matcher.label(period))
+            .unwrap_or_else(|| String::from("."));
+        for copy in tile_monomer_copies(block, monomer) {
+            rows.push(MonomerRow {
+                chrom: rec.query_name().to_owned(),
+                start: copy.start,
+                end: copy.end,
+                monomers: monomer.val.trf_monomer.clone(),
+                strand: rec.strand(),
+                period_class: period_class.clone(),
+                copy: Some(CopyColumns {
+                    copy_idx: Some(copy.copy_idx),
+                    period: Some(monomer.val.trf_period),
+                    is_partial: copy.is_partial,
+                }),
+                local_cols: local_cols.to_owned(),
+                overlaps: None,
+            });
+        }
+    }
+}
+
+/// Local identity columns of a row. ex. `\t170\t2\t1\t0.982659`
+fn format_local_stats(stats: &AlignmentStats) -> String {
+    format!(
+        "\t{}\t{}\t{}\t{:.6}",
+        stats.matches,
+        stats.mismatches,
+        stats.indels(),
+        stats.gap_compressed_identity()
+    )
+}
+
+/// Unique `trf` periods of monomers joined by commas or `.` if none. ex. `171,342`
+fn format_periods(monomers: &[Interval<u32, &Monomer>]) -> String {
+    if monomers.is_empty() {
+        return String::from(".");
+    }
+    monomers
+        .iter()
+        .map(|m| m.val.trf_period)
+        .sorted()
+        .dedup()
+        .join(",")
+}
+
+/// Join monomer sequences with commas in the given order.
+/// * Monomers found on multiple copies of an enlonged motif are only counted once.
+fn join_monomers(
+    monomers: &[Interval<u32, &Monomer>],
+    order: MonomerOrder,
+    monomer_index: &MonomerIndex,
+) -> String {
+    let counts = monomer_index.count(
+        monomers
+            .iter()
+            .unique_by(|m| std::ptr::from_ref(m.val))
+            .map(|m| m.val.trf_monomer.as_str()),
+    );
+    monomer_index.join(&counts, order)
+}
+
+/// Read `trf` monomers or estimate them from `srf` motifs if only a fasta file is provided.
+/// * Motif lengths are taken from the motif sequences if not already known.
+fn read_motif_monomers(
+    monomers: Option<PathBuf>,
+    fa: Option<PathBuf>,
+    min_identity: f32,
+    motif_lens: &mut Option<HashMap<String, u32>>,
+) -> eyre::Result<Option<MotifMonomers>> {
+    if let Some(monomers) = monomers {
+        return Ok(Some(read_trf_monomers(monomers)?));
+    }
+    let Some(fa) = fa else {
+        return Ok(None);
+    };
+    let reader = if fa != OsStr::new("-") {
+        Box::new(BufReader::new(File::open(fa)?)) as Box<dyn BufRead>
+    } else {
+        Box::new(BufReader::new(stdin().lock()))
+    };
+    let records = read_fasta(reader)?;
+    motif_lens.get_or_insert_with(|| {
+        records
+            .iter()
+            .map(|(name, seq)| (name.clone(), seq.len() as u32))
+            .collect()
+    });
+    Ok(Some(estimate_motif_monomers(
+        records
+            .iter()
+            .map(|(name, seq)| (name.as_str(), seq.as_slice())),
+        min_identity,
+    )))
+}
+
+/// Initialize logging at info level adjusted by `-v` and `-q`. `RUST_LOG` takes precedence.
+fn init_logger(verbose: u8, quiet: u8) {
+    let level = match 3 + i16::from(verbose) - i16::from(quiet) {
+        ..=0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .format_target(false)
+        .parse_default_env()
+        .init();
+}
+
 fn main() -> eyre::Result<()> {
-    let cli = Cli::parse();
-    eprintln!("Running command:\n{:#?}", &cli.command);
+    let start = Instant::now();
+    let cmd = Cli::command();
+    let mut matches = cmd.clone().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches)?;
+    // Add options from config not given on the command-line and parse again.
+    let config_args = cli
+        .config
+        .as_ref()
+        .map(|config| Config::from_path(config)?.to_args(&cmd, &matches))
+        .transpose()?;
+    if let Some(config_args) = config_args.as_ref() {
+        matches = cmd
+            .clone()
+            .get_matches_from(std::env::args_os().chain(config_args.iter().cloned()));
+        cli = Cli::from_arg_matches(&matches)?;
+    }
+    // Logging can be set by the config.
+    init_logger(cli.verbose, cli.quiet);
+    let config_args = config_args.unwrap_or_default();
+    if let Some(config) = cli.config.as_ref()
+        && !config_args.is_empty()
+    {
+        info!(
+            "Using options from config ({config:?}): {}",
+            config_args
+                .iter()
+                .map(|arg| arg.to_string_lossy())
+                .join(" ")
+        );
+    }
+    info!(
+        "Running command: {}",
+        std::env::args_os()
+            .chain(config_args)
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .join(" ")
+    );
+    debug!("Parsed command: {:?}", &cli.command);
+    let mut report = RunReport::new(&cmd, &matches);
 
     match cli.command {
         Command::Monomers {
             paf,
             monomers,
+            fa,
+            min_identity,
             outfile,
-            sizes,
-            diff,
+            period:
+                PeriodArgs {
+                    sizes,
+                    diff,
+                    preset,
+                    harmonics,
+                },
             max_seq_div,
+            whole_motif_diff,
+            min_mapq,
+            primary_only,
+            min_aln_len,
+            min_motif_coverage,
+            div_metric,
+            local_identity,
+            local_window,
+            min_local_identity,
+            resolve_overlaps,
+            rejected,
+            tile,
+            motif_fai,
+            order,
         } => {
-            let monomers = read_trf_monomers(monomers)?;
+            let mut motif_lens = motif_fai.map(read_fai).transpose()?;
+            let Some(monomers) = read_motif_monomers(monomers, fa, min_identity, &mut motif_lens)?
+            else {
+                bail!("Either --monomers or --fa is required.")
+            };
             let reader = Reader::from_path(paf)?;
             // Inteval tree of allowed period ranges.
-            let monomer_period_range: Lapper<u32, ()> = create_monomer_range(&sizes, diff);
-            let monomer_periods: HashSet<u32> = HashSet::from_iter(sizes);
+            let period_matcher =
+                PeriodMatcher::new(get_period_specs(&sizes, preset), diff, harmonics);
+            info!("Using monomer period ranges:\n{period_matcher}");
+            let monomer_index = MonomerIndex::new(&period_matcher);
             let mut writer = if let Some(outfile) = outfile {
                 Box::new(BufWriter::new(File::create(outfile)?)) as Box<dyn Write>
             } else {
                 Box::new(BufWriter::new(stdout().lock())) as Box<dyn Write>
             };
 
-            let Some(min_monomer_period) = monomer_periods.iter().min().cloned() else {
+            let Some(min_monomer_period) = period_matcher.min_period() else {
                 bail!("No monomer periods provided.")
             };
 
-            eprintln!(
-                "Using monomer periodicity range:\n{:#?}",
-                monomer_period_range.intervals
-            );
             let null_lapper = Lapper::new(vec![]);
+            let use_local_stats = local_identity || min_local_identity > 0.0;
+            let mut aln_filter =
+                AlignmentFilter::new(min_mapq, primary_only, min_aln_len, min_motif_coverage);
+            let mut overlap_resolver = resolve_overlaps.map(|_| OverlapResolver::default());
+            let mut rejections = Rejections::new(
+                rejected
+                    .map(|path| {
+                        File::create(path)
+                            .map(|file| Box::new(BufWriter::new(file)) as Box<dyn Write>)
+                    })
+                    .transpose()?,
+            )?;
 
             for rec in reader
                 .into_records()
                 .flatten()
                 .sorted_by(|a, b| a.query_start().cmp(&b.query_start()))
             {
+                if let Err(reason) = aln_filter.check(&rec) {
+                    rejections.reject(
+                        rec.query_name(),
+                        rec.query_start(),
+                        rec.query_end(),
+                        rec.target_name(),
+                        RejectReason::Filter(reason),
+                        || reason.values(&rec),
+                    )?;
+                    continue;
+                }
+                let has_trf_monomers = monomers.contains_key(rec.target_name());
                 let target_tr_chrom_monomers =
                     monomers.get(rec.target_name()).unwrap_or(&null_lapper);
+                let motif_len = get_motif_len(rec.target_name(), motif_lens.as_ref());
                 let target_len = rec.target_len() as i32;
                 let aln_len = rec.alignment_block_len() as i32;
                 let aln_itv_diff = target_len.abs_diff(aln_len);
                 let aln_rpt_len_perc_diff = aln_itv_diff as f32 / rec.target_len() as f32;
+                let cg_ops = if use_local_stats || tile {
+                    parse_record_cigar(&rec, rec.cg().context("Record has no cigar.")?)?
+                } else {
+                    vec![]
+                };
+                let mut rows = vec![];
 
-                // If rec is within x% difference in length. Use gap-comprssed identity rather than overlap to find divergent and monomeric HORs.
+                // If rec is within x% difference in length. Use sequence divergence rather than overlap to find divergent and monomeric HORs.
                 // Will not return individual monomer positions but entire region.
-                if aln_rpt_len_perc_diff < diff
-                    && rec.de().map(|de| *de < max_seq_div).unwrap_or_default()
-                {
-                    let mut monomers = target_tr_chrom_monomers
-                        .iter()
-                        .filter_map(|m| {
-                            (monomer_period_range.count(m.val.trf_period, m.val.trf_period) > 0)
-                                .then_some(&m.val.trf_monomer)
-                        })
-                        .join(",");
+                let seq_div = (aln_rpt_len_perc_diff < whole_motif_diff)
+                    .then(|| get_seq_divergence(&rec, div_metric))
+                    .transpose()?;
+                if seq_div.is_some_and(|seq_div| seq_div < max_seq_div) {
+                    let rec_monomers = find_circular(
+                        target_tr_chrom_monomers,
+                        rec.target_start(),
+                        rec.target_end(),
+                        motif_len,
+                    )
+                    .into_iter()
+                    .filter(|m| period_matcher.contains(m.val.trf_period))
+                    .collect_vec();
+                    let mut monomers = join_monomers(&rec_monomers, order, &monomer_index);
 
                     // Allow if motif found is within range even if doesn't haven any monomers.
-                    if monomer_period_range
-                        .count(rec.alignment_block_len(), rec.alignment_block_len())
-                        > 0
-                        && monomers.is_empty()
-                    {
+                    if period_matcher.contains(rec.alignment_block_len()) && monomers.is_empty() {
                         monomers.push('.');
                     } else if monomers.is_empty() {
+                        let reason = if has_trf_monomers {
+                            RejectReason::NoPeriodMatch
+                        } else {
+                            RejectReason::NoTrfMonomers
+                        };
+                        rejections.reject(
+                            rec.query_name(),
+                            rec.query_start(),
+                            rec.query_end(),
+                            rec.target_name(),
+                            reason,
+                            || {
+                                let periods = find_circular(
+                                    target_tr_chrom_monomers,
+                                    rec.target_start(),
+                                    rec.target_end(),
+                                    motif_len,
+                                );
+                                format!(
+                                    "aln_len={};periods={}",
+                                    rec.alignment_block_len(),
+                                    format_periods(&periods)
+                                )
+                            },
+                        )?;
                         continue;
                     }
-                    writeln_w_bp!(
-                        &mut writer,
-                        "{}\t{}\t{}\t{}\t0\t{}\t{}\t{}\t0,0,0",
-                        rec.query_name(),
-                        rec.query_start(),
-                        rec.query_end(),
-                        monomers,
-                        rec.strand(),
-                        rec.query_start(),
-                        rec.query_end(),
-                    );
-                    continue;
-                }
-
-                // Otherwise, search cigar string elements for monomers.
-                let paired_itvs = get_aligned_paired_itvs(&rec, min_monomer_period)?;
-                for (q_itv, t_itv) in paired_itvs {
-                    let ovl = target_tr_chrom_monomers
-                        .find(t_itv.start, t_itv.stop)
-                        .collect_vec();
+                    let local_cols = if use_local_stats {
+                        let stats = AlignmentStats::new(&cg_ops);
+                        let identity = stats.gap_compressed_identity();
+                        if identity < min_local_identity {
+                            rejections.reject(
+                                rec.query_name(),
+                                rec.query_start(),
+                                rec.query_end(),
+                                rec.target_name(),
+                                RejectReason::LowLocalIdentity,
+                                || format!("identity={identity:.6};min_local_identity={min_local_identity}"),
+                            )?;
+                            continue;
+                        }
+                        if local_identity {
+                            format_local_stats(&stats)
+                        } else {
+                            String::new()
+                        }
+                    } else {
+                        String::new()
+                    };
 
-                    if ovl.is_empty() {
-                        continue;
+                    if tile {
+                        let q_itv = Interval {
+                            start: rec.query_start(),
+                            stop: rec.query_end(),
+                            val: (),
+                        };
+                        let t_itv = Interval {
+                            start: rec.target_start(),
+                            stop: rec.target_end(),
+                            val: (),
+                        };
+                        if rec_monomers.is_empty() {
+                            rows.push(MonomerRow {
+                                chrom: rec.query_name().to_owned(),
+                                start: q_itv.start,
+                                end: q_itv.stop,
+                                monomers,
+                                strand: rec.strand(),
+                                period_class: String::from("."),
+                                copy: Some(CopyColumns {
+                                    copy_idx: None,
+                                    period: None,
+                                    is_partial: false,
+                                }),
+                                local_cols: local_cols.clone(),
+                                overlaps: None,
+                            });
+                        }
+                        // Project copies through the cigar so indels don't shift their boundaries.
+                        let block = AlignedBlock {
+                            q_itv,
+                            t_itv,
+                            strand: rec.strand(),
+                            cg_ops: Some(&cg_ops),
+                        };
+                        get_monomer_copy_rows(
+                            &mut rows,
+                            &rec,
+                            &block,
+                            &rec_monomers,
+                            &local_cols,
+                            &period_matcher,
+                        );
+                    } else {
+                        rows.push(MonomerRow {
+                            chrom: rec.query_name().to_owned(),
+                            start: rec.query_start(),
+                            end: rec.query_end(),
+                            period_class: label_period_class(&monomers, &period_matcher),
+                            monomers,
+                            strand: rec.strand(),
+                            copy: None,
+                            local_cols,
+                            overlaps: None,
+                        });
                     }
-                    let q_itv_len = q_itv.stop - q_itv.start;
+                } else {
+                    // Otherwise, search cigar string elements for monomers.
+                    let paired_itvs = if has_trf_monomers {
+                        get_aligned_paired_itvs(&rec, min_monomer_period)?
+                    } else {
+                        rejections.reject(
+                            rec.query_name(),
+                            rec.query_start(),
+                            rec.query_end(),
+                            rec.target_name(),
+                            RejectReason::NoTrfMonomers,
+                            || {
+                                format!(
+                                    "aln_len={};motif_len={}",
+                                    rec.alignment_block_len(),
+                                    motif_len.map_or(String::from("."), |len| len.to_string())
+                                )
+                            },
+                        )?;
+                        vec![]
+                    };
+                    for (q_itv, t_itv) in paired_itvs {
+                        let ovl = find_circular(
+                            target_tr_chrom_monomers,
+                            t_itv.start,
+                            t_itv.stop,
+                            motif_len,
+                        );
 
-                    let monomers = ovl
-                        .iter()
-                        .filter_map(|o| {
-                            let is_period_ovl =
-                                monomer_period_range.count(o.val.trf_period, o.val.trf_period) > 0;
-                            let at_least_period_size = o.val.trf_period <= q_itv_len;
+                        if ovl.is_empty() {
+                            rejections.reject(
+                                rec.query_name(),
+                                q_itv.start,
+                                q_itv.stop,
+                                rec.target_name(),
+                                RejectReason::NoMonomers,
+                                || format!("target_st={};target_end={}", t_itv.start, t_itv.stop),
+                            )?;
+                            continue;
+                        }
+                        let q_itv_len = q_itv.stop - q_itv.start;
 
-                            (is_period_ovl && at_least_period_size).then_some(&o.val.trf_monomer)
-                        })
-                        .join(",");
+                        let (period_ovl, other_ovl): (Vec<_>, Vec<_>) = ovl
+                            .into_iter()
+                            .partition(|o| period_matcher.contains(o.val.trf_period));
+                        if period_ovl.is_empty() {
+                            rejections.reject(
+                                rec.query_name(),
+                                q_itv.start,
+                                q_itv.stop,
+                                rec.target_name(),
+                                RejectReason::NoPeriodMatch,
+                                || format!("periods={}", format_periods(&other_ovl)),
+                            )?;
+                            continue;
+                        }
+                        let (itv_monomers, longer_ovl): (Vec<_>, Vec<_>) = period_ovl
+                            .into_iter()
+                            .partition(|o| o.val.trf_period <= q_itv_len);
 
-                    if monomers.is_empty() {
-                        continue;
+                        if itv_monomers.is_empty() {
+                            rejections.reject(
+                                rec.query_name(),
+                                q_itv.start,
+                                q_itv.stop,
+                                rec.target_name(),
+                                RejectReason::ShorterThanPeriod,
+                                || {
+                                    format!(
+                                        "len={q_itv_len};periods={}",
+                                        format_periods(&longer_ovl)
+                                    )
+                                },
+                            )?;
+                            continue;
+                        }
+                        let local_cols = if use_local_stats {
+                            // Window around aligned block or footprint of its monomers.
+                            let (start, stop) = if let Some(window) = local_window {
+                                (t_itv.start.saturating_sub(window), t_itv.stop + window)
+                            } else {
+                                itv_monomers.iter().fold((u32::MAX, 0), |(st, end), m| {
+                                    (st.min(m.start), end.max(m.stop))
+                                })
+                            };
+                            let stats = AlignmentStats::from_target_window(
+                                &cg_ops,
+                                rec.target_start(),
+                                start,
+                                stop,
+                            );
+                            let identity = stats.gap_compressed_identity();
+                            if identity < min_local_identity {
+                                rejections.reject(
+                                    rec.query_name(),
+                                    q_itv.start,
+                                    q_itv.stop,
+                                    rec.target_name(),
+                                    RejectReason::LowLocalIdentity,
+                                    || format!("identity={identity:.6};min_local_identity={min_local_identity}"),
+                                )?;
+                                continue;
+                            }
+                            if local_identity {
+                                format_local_stats(&stats)
+                            } else {
+                                String::new()
+                            }
+                        } else {
+                            String::new()
+                        };
+                        if tile {
+                            let block = AlignedBlock {
+                                q_itv,
+                                t_itv,
+                                strand: rec.strand(),
+                                cg_ops: None,
+                            };
+                            get_monomer_copy_rows(
+                                &mut rows,
+                                &rec,
+                                &block,
+                                &itv_monomers,
+                                &local_cols,
+                                &period_matcher,
+                            );
+                            continue;
+                        }
+                        let monomers = join_monomers(&itv_monomers, order, &monomer_index);
+                        rows.push(MonomerRow {
+                            chrom: rec.query_name().to_owned(),
+                            start: q_itv.start,
+                            end: q_itv.stop,
+                            period_class: label_period_class(&monomers, &period_matcher),
+                            monomers,
+                            strand: rec.strand(),
+                            copy: None,
+                            local_cols,
+                            overlaps: None,
+                        });
                     }
-                    writeln_w_bp!(
-                        &mut writer,
-                        "{}\t{}\t{}\t{}\t0\t{}\t{}\t{}\t0,0,0",
+                }
+
+                // Too divergent to use as a whole and no cigar block had monomers either.
+                if let Some(seq_div) = seq_div.filter(|seq_div| *seq_div >= max_seq_div)
+                    && has_trf_monomers
+                    && rows.is_empty()
+                {
+                    rejections.reject(
                         rec.query_name(),
-                        q_itv.start,
-                        q_itv.stop,
-                        monomers,
-                        rec.strand(),
-                        q_itv.start,
-                        q_itv.stop,
-                    );
+                        rec.query_start(),
+                        rec.query_end(),
+                        rec.target_name(),
+                        RejectReason::HighDivergence,
+                        || format!("seq_div={seq_div:.6};max_seq_div={max_seq_div}"),
+                    )?;
+                }
+                if let (Some(resolver), Some(score)) = (overlap_resolver.as_mut(), resolve_overlaps)
+                {
+                    resolver.add(&rec, get_overlap_score(&rec, score, div_metric)?, rows);
+                } else {
+                    for row in rows {
+                        report.add_row_bp(&row);
+                        writeln_w_bp!(&mut writer, "{row}");
+                    }
                 }
             }
+            if let Some(resolver) = overlap_resolver {
+                for row in resolver.resolve() {
+                    report.add_row_bp(&row);
+                    writeln_w_bp!(&mut writer, "{row}");
+                }
+            }
+            info!("Filtered PAF records:\n{aln_filter}");
+            info!("Rejected records and intervals:\n{rejections}");
+            report.records = Some(RecordCounts::new(&aln_filter, &rejections));
         }
         Command::Motifs {
             fa,
             monomers,
             outfile,
-            sizes,
-            diff,
+            period:
+                PeriodArgs {
+                    sizes,
+                    diff,
+                    preset,
+                    harmonics,
+                },
             require_all,
+            min_identity,
         } => {
             let reader = if fa != OsStr::new("-") {
                 Box::new(BufReader::new(File::open(fa)?)) as Box<dyn BufRead>
             } else {
                 Box::new(BufReader::new(stdin().lock()))
             };
-            let monomers = read_trf_monomers(monomers)?;
+            let records = read_fasta(reader)?;
+            let monomers = if let Some(monomers) = monomers {
+                read_trf_monomers(monomers)?
+            } else {
+                estimate_motif_monomers(
+                    records
+                        .iter()
+                        .map(|(name, seq)| (name.as_str(), seq.as_slice())),
+                    min_identity,
+                )
+            };
 
             let mut writer = if let Some(outfile) = outfile {
                 Box::new(BufWriter::new(File::create(outfile)?)) as Box<dyn Write>
             } else {
                 Box::new(BufWriter::new(stdout().lock())) as Box<dyn Write>
             };
-            let monomer_period_range: Lapper<u32, ()> = create_monomer_range(&sizes, diff);
-
-            let mut curr_rec: Option<String> = None;
-            for line in reader.lines().map_while(Result::ok) {
-                if line.starts_with('>') {
-                    curr_rec = line.trim().strip_prefix('>').and_then(|name| {
-                        name.split_once(' ')
-                            .map(|(name, _comments)| name.to_owned())
-                    });
+            let period_matcher =
+                PeriodMatcher::new(get_period_specs(&sizes, preset), diff, harmonics);
+            info!("Using monomer period ranges:\n{period_matcher}");
+
+            for (rec_name, seq) in records.iter() {
+                let Some(rec_monomers) = monomers.get(rec_name) else {
                     continue;
+                };
+                let is_valid_motif = if require_all {
+                    rec_monomers
+                        .iter()
+                        .all(|mon| period_matcher.contains(mon.val.trf_period))
+                } else {
+                    rec_monomers
+                        .iter()
+                        .any(|mon| period_matcher.contains(mon.val.trf_period))
+                };
+                if is_valid_motif {
+                    writeln_w_bp!(&mut writer, ">{rec_name}");
+                    writeln_w_bp!(&mut writer, "{}", String::from_utf8_lossy(seq));
                 }
-                if let Some((rec_name, rec_monomers)) = curr_rec
-                    .as_ref()
-                    .and_then(|rec_name| monomers.get(rec_name).map(|mons| (rec_name, mons)))
-                {
-                    let is_valid_motif = if require_all {
-                        rec_monomers.iter().all(|mon| {
-                            monomer_period_range.count(mon.val.trf_period, mon.val.trf_period) > 0
-                        })
-                    } else {
-                        rec_monomers.iter().any(|mon| {
-                            monomer_period_range.count(mon.val.trf_period, mon.val.trf_period) > 0
-                        })
-                    };
-                    if is_valid_motif {
-                        writeln_w_bp!(&mut writer, ">{rec_name}");
-                        writeln_w_bp!(&mut writer, "{line}");
-                    }
+            }
+        }
+        Command::Enlong {
+            fa,
+            outfile,
+            min_len,
+            lengths_outfile,
+        } => {
+            let reader = if fa != OsStr::new("-") {
+                Box::new(BufReader::new(File::open(fa)?)) as Box<dyn BufRead>
+            } else {
+                Box::new(BufReader::new(stdin().lock()))
+            };
+            let mut writer = if let Some(outfile) = outfile {
+                Box::new(BufWriter::new(File::create(outfile)?)) as Box<dyn Write>
+            } else {
+                Box::new(BufWriter::new(stdout().lock())) as Box<dyn Write>
+            };
+            let mut lengths_writer = lengths_outfile
+                .map(|lengths_outfile| File::create(lengths_outfile).map(BufWriter::new))
+                .transpose()?;
+
+            for (rec_name, seq) in read_fasta(reader)? {
+                if seq.is_empty() {
+                    continue;
+                }
+                // Circular motifs can be concatenated with themselves.
+                let n_copies = (min_len as usize).div_ceil(seq.len()).max(1);
+                writeln_w_bp!(&mut writer, ">{rec_name}");
+                writeln_w_bp!(
+                    &mut writer,
+                    "{}",
+                    String::from_utf8_lossy(&seq.repeat(n_copies))
+                );
+                if let Some(lengths_writer) = lengths_writer.as_mut() {
+                    writeln_w_bp!(lengths_writer, "{rec_name}\t{}", seq.len());
                 }
             }
         }
@@ -230,8 +746,21 @@ fn main() -> eyre::Result<()> {
             outfile,
             dst,
             min_len,
-            sizes,
-            diff,
+            period:
+                PeriodArgs {
+                    sizes,
+                    diff,
+                    preset,
+                    harmonics,
+                },
+            by_period,
+            min_density,
+            window,
+            summary,
+            order,
+            sorted,
+            no_monomer_filter,
+            min_hits,
         } => {
             let reader = if bed != OsStr::new("-") {
                 Box::new(BufReader::new(File::open(bed)?)) as Box<dyn BufRead>
@@ -243,78 +772,445 @@ fn main() -> eyre::Result<()> {
             } else {
                 Box::new(BufWriter::new(stdout().lock())) as Box<dyn Write>
             };
-            let monomer_period_range: Lapper<u32, ()> = create_monomer_range(&sizes, diff);
-            eprintln!(
-                "Using monomer periodicity range:\n{:#?}",
-                monomer_period_range.intervals
-            );
-
-            let mut final_intervals: Vec<(String, u32, u32, HashSet<String>)> = vec![];
-            let mut intervals: VecDeque<(String, u32, u32, HashSet<String>)> = reader
-                .lines()
-                .map_while(Result::ok)
-                .map(|line| {
-                    let (chrom, st, end, monomers, _, _, _, _, _) =
-                        line.split('\t').collect_tuple().unwrap();
-                    (
-                        chrom.to_owned(),
-                        st.parse::<u32>().unwrap(),
-                        end.parse::<u32>().unwrap(),
-                        monomers.split(',').map(|m| m.to_owned()).collect(),
-                    )
-                })
-                .sorted_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)))
-                .collect();
-
-            // Merge intervals.
-            while let Some(mut itv_1) = intervals.pop_front() {
-                let Some(itv_2) = intervals.pop_front() else {
-                    // Remove anything that isn't in required monomer period range.
-                    itv_1.3.retain(|m| {
-                        monomer_period_range.count(m.len() as u32, m.len() as u32) != 0
-                    });
+            let period_matcher =
+                PeriodMatcher::new(get_period_specs(&sizes, preset), diff, harmonics);
+            info!("Using monomer period ranges:\n{period_matcher}");
+
+            let monomer_index = MonomerIndex::new(&period_matcher);
+            let filter = RegionFilter {
+                min_len,
+                require_periods: !no_monomer_filter,
+                min_hits,
+            };
+            let n_read = Cell::new(0);
+            let records = reader.lines().enumerate().filter_map(|(i, line)| {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(err) => return Some(Err(err.into())),
+                };
+                parse_bed_line(&line, i + 1)
+                    .map(|rec| rec.map(|rec| BedInterval::new(rec, &monomer_index)))
+                    .transpose()
+                    .inspect(|_| n_read.set(n_read.get() + 1))
+            });
+            let intervals: Box<dyn Iterator<Item = eyre::Result<BedInterval>> + '_> = if sorted {
+                // Stream intervals and check that they're sorted.
+                let mut sorted_check = SortedCheck::default();
+                Box::new(records.map(move |itv| {
+                    let itv = itv?;
+                    sorted_check.check(&itv)?;
+                    Ok(itv)
+                }))
+            } else {
+                let mut intervals: Vec<BedInterval> = records.collect::<eyre::Result<_>>()?;
+                intervals.sort_by(|a, b| (&a.chrom, a.start).cmp(&(&b.chrom, b.start)));
+                Box::new(intervals.into_iter().map(Ok))
+            };
 
-                    final_intervals.push(itv_1);
-                    break;
+            let write_region = |writer: &mut Box<dyn Write>,
+                                report: &mut RunReport,
+                                region: &Region,
+                                period: Option<u32>,
+                                name: &str| {
+                let (chrom, st, end) = (&region.chrom, region.start, region.end);
+                report.add_written(period, (end - st) as u64, &period_matcher);
+                let period_class = period
+                    .map(|period| period_matcher.label(period))
+                    .unwrap_or_else(|| String::from("."));
+                // Score is the fraction of bases covered by hits with density-based merging.
+                let score = if min_density.is_some() {
+                    format!("{:.4}", region.frac_covered())
+                } else {
+                    String::from("0")
                 };
-                let dst_between = itv_2.1.saturating_sub(itv_1.2);
-                // Must be same name and within distance.
-                if dst_between <= dst && itv_1.0 == itv_2.0 {
-                    intervals.push_front((
-                        itv_1.0,
-                        itv_1.1,
-                        itv_2.2,
-                        itv_1
-                            .3
-                            .union(&itv_2.3)
-                            .cloned()
-                            .collect::<HashSet<String>>(),
-                    ));
+                if summary {
+                    writeln_w_bp!(
+                        writer,
+                        "{chrom}\t{st}\t{end}\t{name}\t{score}\t.\t{st}\t{end}\t0,0,0\t{period_class}\t{}",
+                        region.summary_columns()
+                    );
                 } else {
-                    let final_itv_len = itv_1.2 - itv_1.1;
-                    // Filter monomers that don't fall within monomer period range.
-                    itv_1.3.retain(|m| {
-                        monomer_period_range.count(m.len() as u32, m.len() as u32) != 0
-                    });
+                    writeln_w_bp!(
+                        writer,
+                        "{chrom}\t{st}\t{end}\t{name}\t{score}\t.\t{st}\t{end}\t0,0,0\t{period_class}"
+                    );
+                }
+            };
+            let write_monomer_region =
+                |writer: &mut Box<dyn Write>, report: &mut RunReport, region: &Region| {
+                    let mut monomers = monomer_index.join(&region.monomers, order);
+                    if monomers.is_empty() {
+                        monomers.push('.');
+                    }
+                    write_region(
+                        writer,
+                        report,
+                        region,
+                        region.monomers.period_class(),
+                        &monomers,
+                    );
+                };
 
-                    if final_itv_len > min_len && !itv_1.3.is_empty() {
-                        final_intervals.push(itv_1);
+            match (by_period, min_density) {
+                (false, None) => {
+                    let mut merger = RegionMerger::new(dst, filter.clone());
+                    for itv in intervals {
+                        if let Some(region) = merger.push(itv?) {
+                            write_monomer_region(&mut writer, &mut report, &region);
+                        }
+                    }
+                    if let Some(region) = merger.finish() {
+                        write_monomer_region(&mut writer, &mut report, &region);
+                    }
+                }
+                (false, Some(min_density)) => {
+                    let regions = intervals.process_results(|itvs| {
+                        merge_intervals_by_density(itvs, window, min_density, &filter)
+                    })?;
+                    for region in regions {
+                        write_monomer_region(&mut writer, &mut report, &region);
+                    }
+                }
+                (true, min_density) => {
+                    // Merge each period class separately and label regions by class.
+                    let mut period_regions: Vec<(u32, Region)> = vec![];
+                    if let Some(min_density) = min_density {
+                        let mut period_intervals: BTreeMap<u32, Vec<BedInterval>> = BTreeMap::new();
+                        for itv in intervals {
+                            for (period, itv) in split_by_period(itv?) {
+                                period_intervals.entry(period).or_default().push(itv);
+                            }
+                        }
+                        for (period, itvs) in period_intervals {
+                            period_regions.extend(
+                                merge_intervals_by_density(itvs, window, min_density, &filter)
+                                    .into_iter()
+                                    .map(|region| (period, region)),
+                            );
+                        }
+                    } else {
+                        let mut mergers: BTreeMap<u32, RegionMerger> = BTreeMap::new();
+                        for itv in intervals {
+                            for (period, itv) in split_by_period(itv?) {
+                                let merger = mergers
+                                    .entry(period)
+                                    .or_insert_with(|| RegionMerger::new(dst, filter.clone()));
+                                period_regions.extend(merger.push(itv).map(|r| (period, r)));
+                            }
+                        }
+                        for (period, merger) in mergers {
+                            period_regions.extend(merger.finish().map(|r| (period, r)));
+                        }
+                    }
+                    for (period, region) in period_regions.iter().sorted_by(|(p1, r1), (p2, r2)| {
+                        (&r1.chrom, r1.start, p1).cmp(&(&r2.chrom, r2.start, p2))
+                    }) {
+                        write_region(
+                            &mut writer,
+                            &mut report,
+                            region,
+                            Some(*period),
+                            &period_matcher.label(*period),
+                        );
                     }
-                    intervals.push_front(itv_2);
                 }
             }
-            for (chrom, st, end, monomers) in final_intervals {
-                let mut monomers = monomers.iter().join(",");
-                if monomers.is_empty() {
-                    monomers.push('.');
+            report.set_rows_read(n_read.get());
+        }
+        Command::Extract {
+            bed,
+            assembly,
+            fai,
+            outfile,
+            monomer_outfile,
+            period:
+                PeriodArgs {
+                    sizes,
+                    diff,
+                    preset,
+                    harmonics,
+                },
+            revcomp,
+        } => {
+            let reader = if bed != OsStr::new("-") {
+                Box::new(BufReader::new(File::open(bed)?)) as Box<dyn BufRead>
+            } else {
+                Box::new(BufReader::new(stdin().lock()))
+            };
+            let mut writer = if let Some(outfile) = outfile {
+                Box::new(BufWriter::new(File::create(outfile)?)) as Box<dyn Write>
+            } else {
+                Box::new(BufWriter::new(stdout().lock())) as Box<dyn Write>
+            };
+            let mut fasta = IndexedFasta::from_path(assembly, fai)?;
+            let period_matcher =
+                PeriodMatcher::new(get_period_specs(&sizes, preset), diff, harmonics);
+            info!("Using monomer period ranges:\n{period_matcher}");
+            let monomer_index = MonomerIndex::new(&period_matcher);
+
+            let mut n_read = 0;
+            for (i, line) in reader.lines().enumerate() {
+                let line = line?;
+                let Some(rec) = parse_bed_line(&line, i + 1)? else {
+                    continue;
+                };
+                n_read += 1;
+                // Names of `regions --by-period` output are period classes rather than monomers.
+                let monomers = rec
+                    .name
+                    .filter(|name| Some(*name) != rec.period_class)
+                    .map(|monomers| monomer_index.count(monomers.split(',')))
+                    .unwrap_or_default();
+                let period_class = get_bed_period_class(&rec, &period_matcher);
+                let period = period_class
+                    .map(|period| period_matcher.label(period))
+                    .unwrap_or_else(|| String::from("."));
+                let monomer_ids = if monomers.is_empty() {
+                    String::from(".")
+                } else {
+                    monomers.iter().map(|(id, _)| format!("m{id}")).join(",")
+                };
+                let strand = rec.strand.unwrap_or('.');
+
+                let mut seq = fasta
+                    .fetch(rec.chrom, rec.start, rec.end)
+                    .with_context(|| format!("Invalid interval on line {}.", i + 1))?;
+                if revcomp && strand == '-' {
+                    seq = reverse_complement(&seq);
                 }
                 writeln_w_bp!(
                     &mut writer,
-                    "{chrom}\t{st}\t{end}\t{monomers}\t0\t.\t{st}\t{end}\t0,0,0"
+                    ">{}:{}-{}({strand}) period={period} monomers={monomer_ids}",
+                    rec.chrom,
+                    rec.start,
+                    rec.end,
+                );
+                writeln_w_bp!(&mut writer, "{}", String::from_utf8_lossy(&seq));
+                report.add_written(period_class, (rec.end - rec.start) as u64, &period_matcher);
+            }
+            report.set_rows_read(n_read);
+
+            if let Some(monomer_outfile) = monomer_outfile {
+                let mut monomer_writer = BufWriter::new(File::create(monomer_outfile)?);
+                for id in 0..monomer_index.len() as u32 {
+                    if let Some(monomer) = monomer_index.get(id) {
+                        writeln_w_bp!(&mut monomer_writer, ">m{id}\n{monomer}");
+                    }
+                }
+            }
+        }
+        Command::Stats {
+            bed,
+            fai,
+            outfile,
+            period:
+                PeriodArgs {
+                    sizes,
+                    diff,
+                    preset,
+                    harmonics,
+                },
+            format,
+        } => {
+            let reader = if bed != OsStr::new("-") {
+                Box::new(BufReader::new(File::open(bed)?)) as Box<dyn BufRead>
+            } else {
+                Box::new(BufReader::new(stdin().lock()))
+            };
+            let mut writer = if let Some(outfile) = outfile {
+                Box::new(BufWriter::new(File::create(outfile)?)) as Box<dyn Write>
+            } else {
+                Box::new(BufWriter::new(stdout().lock())) as Box<dyn Write>
+            };
+            let contig_lens = fai.map(read_fai).transpose()?;
+            let period_matcher =
+                PeriodMatcher::new(get_period_specs(&sizes, preset), diff, harmonics);
+            info!("Using monomer period ranges:\n{period_matcher}");
+
+            let stats = summarize_bed(reader, &period_matcher, contig_lens.as_ref())?;
+            // Totals per period class across contigs.
+            for row in stats
+                .iter()
+                .filter(|row| row.chrom == ALL && row.period != ALL)
+            {
+                report
+                    .bp_per_period_class
+                    .insert(row.period.clone(), row.bp);
+            }
+            match format {
+                OutputFormat::Tsv => {
+                    writeln_w_bp!(&mut writer, "{STATS_TSV_HEADER}");
+                    for row in stats {
+                        writeln_w_bp!(&mut writer, "{}", row.to_tsv_row());
+                    }
+                }
+                OutputFormat::Json => {
+                    writeln_w_bp!(&mut writer, "{}", serde_json::to_string_pretty(&stats)?);
+                }
+            }
+        }
+        Command::Periods {
+            paf,
+            monomers,
+            outfile,
+            peaks_outfile,
+            diff,
+            min_frac,
+            max_multiple,
+            motif_fai,
+        } => {
+            let monomers = read_trf_monomers(monomers)?;
+            let motif_lens = motif_fai.map(read_fai).transpose()?;
+            let reader = Reader::from_path(paf)?;
+            let mut writer = if let Some(outfile) = outfile {
+                Box::new(BufWriter::new(File::create(outfile)?)) as Box<dyn Write>
+            } else {
+                Box::new(BufWriter::new(stdout().lock())) as Box<dyn Write>
+            };
+
+            // Weight each monomer period by its aligned base pairs.
+            let mut spectrum = PeriodSpectrum::default();
+            for rec in reader.into_records().flatten() {
+                let Some(target_monomers) = monomers.get(rec.target_name()) else {
+                    continue;
+                };
+                let motif_len = get_motif_len(rec.target_name(), motif_lens.as_ref());
+                for monomer in find_circular(
+                    target_monomers,
+                    rec.target_start(),
+                    rec.target_end(),
+                    motif_len,
+                ) {
+                    let aligned_bp = monomer
+                        .stop
+                        .min(rec.target_end())
+                        .saturating_sub(monomer.start.max(rec.target_start()));
+                    spectrum.add(monomer.val.trf_period, aligned_bp as u64);
+                }
+            }
+            let peaks = spectrum.find_peaks(diff, min_frac, max_multiple);
+            let total_bp = spectrum.total_bp().max(1) as f64;
+
+            writeln_w_bp!(&mut writer, "{SPECTRUM_TSV_HEADER}");
+            for (period, bp) in spectrum.iter() {
+                let peak = peaks
+                    .iter()
+                    .filter(|peak| (peak.min..=peak.max).contains(&period))
+                    .max_by_key(|peak| peak.bp)
+                    .map(|peak| peak.period.to_string())
+                    .unwrap_or_else(|| String::from("."));
+                writeln_w_bp!(
+                    &mut writer,
+                    "{period}\t{bp}\t{:.6}\t{peak}",
+                    bp as f64 / total_bp
+                );
+            }
+            if let Some(peaks_outfile) = peaks_outfile {
+                let mut peaks_writer = BufWriter::new(File::create(peaks_outfile)?);
+                writeln_w_bp!(&mut peaks_writer, "{PEAKS_TSV_HEADER}");
+                for peak in peaks.iter() {
+                    let harmonic = peak.harmonic.unwrap_or(Harmonic {
+                        multiple: 1,
+                        base: peak.period,
+                    });
+                    writeln_w_bp!(
+                        &mut peaks_writer,
+                        "{}\t{}\t{}\t{}\t{:.6}\t{}\t{}",
+                        peak.period,
+                        peak.min,
+                        peak.max,
+                        peak.bp,
+                        peak.bp as f64 / total_bp,
+                        harmonic.base,
+                        harmonic.multiple
+                    );
+                }
+            }
+
+            // Suggest base peaks as sizes and harmonics for their multiples.
+            let sizes = peaks
+                .iter()
+                .filter(|peak| peak.harmonic.is_none())
+                .map(|peak| period_spec(peak.period, diff))
+                .join(" ");
+            let harmonics = peaks
+                .iter()
+                .filter_map(|peak| peak.harmonic.map(|harmonic| harmonic.multiple))
+                .max()
+                .unwrap_or(1);
+            info!("Suggested monomer periods:\n--sizes {sizes} --harmonics {harmonics}");
+        }
+        Command::Abundance {
+            paf,
+            monomers,
+            fa,
+            min_identity,
+            outfile,
+            motif_fai,
+            by_chrom,
+        } => {
+            let mut motif_lens = motif_fai.map(read_fai).transpose()?;
+            let monomers = read_motif_monomers(monomers, fa, min_identity, &mut motif_lens)?;
+            let reader = Reader::from_path(paf)?;
+            let mut writer = if let Some(outfile) = outfile {
+                Box::new(BufWriter::new(File::create(outfile)?)) as Box<dyn Write>
+            } else {
+                Box::new(BufWriter::new(stdout().lock())) as Box<dyn Write>
+            };
+
+            let mut abundances: HashMap<(Option<String>, String), MotifAbundance> = HashMap::new();
+            for rec in reader.into_records().flatten() {
+                let chrom = by_chrom.then(|| rec.query_name().to_owned());
+                abundances
+                    .entry((chrom, rec.target_name().to_owned()))
+                    .or_default()
+                    .add(&rec)?;
+            }
+
+            if by_chrom {
+                writeln_w_bp!(&mut writer, "chrom\t{ABUNDANCE_TSV_HEADER}");
+            } else {
+                writeln_w_bp!(&mut writer, "{ABUNDANCE_TSV_HEADER}");
+            }
+            for ((chrom, motif), abundance, aligned_bp) in abundances
+                .into_iter()
+                .map(|((chrom, motif), abundance)| {
+                    let aligned_bp = abundance.aligned_bp();
+                    ((chrom, motif), abundance, aligned_bp)
+                })
+                .sorted_by(
+                    |((chrom_1, motif_1), _, bp_1), ((chrom_2, motif_2), _, bp_2)| {
+                        chrom_1
+                            .cmp(chrom_2)
+                            .then(bp_2.cmp(bp_1))
+                            .then(motif_1.cmp(motif_2))
+                    },
+                )
+            {
+                let motif_len = get_motif_len(&motif, motif_lens.as_ref())
+                    .map(|len| len.to_string())
+                    .unwrap_or_else(|| String::from("NA"));
+                let mean_de = abundance
+                    .mean_de()
+                    .map(|de| format!("{de:.6}"))
+                    .unwrap_or_else(|| String::from("NA"));
+                let periods = monomers
+                    .as_ref()
+                    .and_then(|monomers| monomers.get(&motif))
+                    .map(period_composition)
+                    .unwrap_or_else(|| String::from("."));
+                let chrom = chrom.map(|chrom| format!("{chrom}\t")).unwrap_or_default();
+                writeln_w_bp!(
+                    &mut writer,
+                    "{chrom}{motif}\t{motif_len}\t{aligned_bp}\t{}\t{mean_de}\t{periods}",
+                    abundance.n_alignments,
                 );
             }
         }
     }
 
+    if let Some(path) = cli.report {
+        report.write(path, start.elapsed())?;
+    }
     Ok(())
 }
@@ -0,0 +1,237 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::{
+    cli::MonomerOrder,
+    io::BedRecord,
+    period::{PeriodMatcher, get_period_class},
+};
+use itertools::Itertools;
+
+/// Period class of a BED row.
+/// * Uses the period class column if it's `.` or a label of the [`PeriodMatcher`]. ex. `regions --by-period` output
+/// * Otherwise, uses the monomers in the name column. See [`get_name_period_class`].
+pub fn get_bed_period_class(rec: &BedRecord, period_matcher: &PeriodMatcher) -> Option<u32> {
+    match rec.period_class {
+        Some(".") => None,
+        Some(label) if let Some(period) = period_matcher.period_from_label(label) => Some(period),
+        _ => rec
+            .name
+            .and_then(|monomers| get_name_period_class(monomers, period_matcher)),
+    }
+}
+
+/// Split a monomer from its count. ex. `seq:12` -> (`seq`, 12)
+/// * Monomers without a count have a count of 1.
+pub fn split_monomer_count(monomer: &str) -> (&str, usize) {
+    monomer
+        .rsplit_once(':')
+        .and_then(|(monomer, count)| Some((monomer, count.parse().ok()?)))
+        .unwrap_or((monomer, 1))
+}
+
+/// Period class of comma-delimited monomers from a name column by their lengths. ex. `seq1,seq2:12`
+/// * See [`get_period_class`].
+pub fn get_name_period_class(monomers: &str, period_matcher: &PeriodMatcher) -> Option<u32> {
+    get_period_class(
+        monomers.split(',').filter_map(|monomer| {
+            period_matcher.period(split_monomer_count(monomer).0.len() as u32)
+        }),
+    )
+}
+
+#[derive(Debug, Default)]
+struct InternedMonomers {
+    ids: HashMap<String, u32>,
+    monomers: Vec<String>,
+}
+
+/// Interned monomer sequences.
+/// * Monomers are stored once and referred to by compact IDs along with their period class.
+#[derive(Debug)]
+pub struct MonomerIndex<'a> {
+    period_matcher: &'a PeriodMatcher,
+    inner: RefCell<InternedMonomers>,
+}
+
+impl<'a> MonomerIndex<'a> {
+    pub fn new(period_matcher: &'a PeriodMatcher) -> Self {
+        MonomerIndex {
+            period_matcher,
+            inner: RefCell::new(InternedMonomers::default()),
+        }
+    }
+
+    /// Get the ID of a monomer, adding it if not seen before.
+    pub fn get_or_insert(&self, monomer: &str) -> u32 {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(id) = inner.ids.get(monomer) {
+            return *id;
+        }
+        let id = inner.monomers.len() as u32;
+        inner.ids.insert(monomer.to_owned(), id);
+        inner.monomers.push(monomer.to_owned());
+        id
+    }
+
+    /// Get a monomer by its ID.
+    pub fn get(&self, id: u32) -> Option<String> {
+        self.inner.borrow().monomers.get(id as usize).cloned()
+    }
+
+    /// Number of monomers.
+    pub fn len(&self) -> usize {
+        self.inner.borrow().monomers.len()
+    }
+
+    /// Count monomers that may have counts. ex. `seq:12`
+    pub fn count<'m>(&self, monomers: impl IntoIterator<Item = &'m str>) -> MonomerCounts {
+        let mut counts = MonomerCounts::default();
+        for monomer in monomers {
+            let (monomer, count) = split_monomer_count(monomer);
+            let period = self.period_matcher.period(monomer.len() as u32);
+            counts.add(self.get_or_insert(monomer), period, count);
+        }
+        counts
+    }
+
+    /// Join monomers with commas in the given order.
+    pub fn join(&self, counts: &MonomerCounts, order: MonomerOrder) -> String {
+        let inner = self.inner.borrow();
+        let get_monomer = |id: u32| inner.monomers[id as usize].as_str();
+        match order {
+            MonomerOrder::First => counts.iter().map(|(id, _)| get_monomer(id)).join(","),
+            MonomerOrder::Abundance => counts
+                .iter()
+                .sorted_by(|(_, count_1), (_, count_2)| count_2.count.cmp(&count_1.count))
+                .map(|(id, count)| format!("{}:{}", get_monomer(id), count.count))
+                .join(","),
+            MonomerOrder::Lexicographic => counts
+                .iter()
+                .map(|(id, _)| get_monomer(id))
+                .sorted()
+                .join(","),
+        }
+    }
+}
+
+/// Occurrences of a monomer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonomerCount {
+    /// Order of first occurrence.
+    pub idx: usize,
+    pub count: usize,
+    pub period: Option<u32>,
+}
+
+/// Monomer IDs with their order of first occurrence and number of occurrences.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MonomerCounts(HashMap<u32, MonomerCount>);
+
+impl MonomerCounts {
+    pub fn add(&mut self, id: u32, period: Option<u32>, count: usize) {
+        let n_monomers = self.0.len();
+        self.0
+            .entry(id)
+            .or_insert(MonomerCount {
+                idx: n_monomers,
+                count: 0,
+                period,
+            })
+            .count += count;
+    }
+
+    pub fn extend(&mut self, other: &MonomerCounts) {
+        for (id, count) in other.iter() {
+            self.add(id, count.period, count.count);
+        }
+    }
+
+    /// Remove monomers that don't fall within monomer period range.
+    pub fn retain_in_period(&mut self) {
+        self.0.retain(|_, count| count.period.is_some());
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate through monomer IDs and their counts in order of first occurrence.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, MonomerCount)> {
+        self.0
+            .iter()
+            .map(|(id, count)| (*id, *count))
+            .sorted_by_key(|(_, count)| count.idx)
+    }
+
+    /// Period class of the monomers. See [`get_period_class`].
+    pub fn period_class(&self) -> Option<u32> {
+        get_period_class(self.0.values().filter_map(|count| count.period))
+    }
+}
+
+/// Monomer copy columns added with `--tile`. Rows without monomers have no copy index or period.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyColumns {
+    pub copy_idx: Option<u32>,
+    pub period: Option<u32>,
+    pub is_partial: bool,
+}
+
+/// Output row of `monomers` with columns:
+/// `chrom, st, end, monomers, 0, strand, st, end, '0,0,0', period_class`
+/// * Followed by optional copy, local identity, and overlap columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonomerRow {
+    pub chrom: String,
+    pub start: u32,
+    pub end: u32,
+    pub monomers: String,
+    pub strand: char,
+    /// Period class labeled by family and harmonic or `.` if none. ex. `alpha_171x2`
+    pub period_class: String,
+    pub copy: Option<CopyColumns>,
+    /// Preformatted local identity columns. ex. `\t170\t2\t1\t0.982659`
+    pub local_cols: String,
+    /// Overlapping alignments of other motifs with `--resolve-overlaps`.
+    pub overlaps: Option<String>,
+}
+
+impl std::fmt::Display for MonomerRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t0\t{}\t{}\t{}\t0,0,0\t{}",
+            self.chrom,
+            self.start,
+            self.end,
+            self.monomers,
+            self.strand,
+            self.start,
+            self.end,
+            self.period_class
+        )?;
+        if let Some(copy) = &self.copy {
+            let fmt_opt = |value: Option<u32>| {
+                value
+                    .map(|value| value.to_string())
+                    .unwrap_or_else(|| String::from("."))
+            };
+            write!(
+                f,
+                "\t{}\t{}\t{}",
+                fmt_opt(copy.copy_idx),
+                fmt_opt(copy.period),
+                u8::from(copy.is_partial)
+            )?;
+        }
+        write!(f, "{}", self.local_cols)?;
+        if let Some(overlaps) = &self.overlaps {
+            write!(f, "\t{overlaps}")?;
+        }
+        Ok(())
+    }
+}
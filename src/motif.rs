@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use rust_lapper::{Interval, Lapper};
+
+use crate::io::Monomer;
+
+/// Parse the original motif length from an `srf` motif name.
+/// * ex. `prefix#circ2-1706` -> `1706`
+pub fn parse_motif_len(name: &str) -> Option<u32> {
+    let (_, len) = name.rsplit_once('-')?;
+    len.parse().ok()
+}
+
+/// Get the original length of an `srf` motif.
+/// * Uses lengths from a `.fai` index if provided, otherwise parses the motif name.
+pub fn get_motif_len(name: &str, motif_lens: Option<&HashMap<String, u32>>) -> Option<u32> {
+    motif_lens
+        .and_then(|lens| lens.get(name).cloned())
+        .or_else(|| parse_motif_len(name))
+        .filter(|len| *len != 0)
+}
+
+/// Find `trf` monomers overlapping a target interval on a circular `srf` motif.
+/// * Target positions are reduced modulo the motif length so monomers are found on any copy of an enlonged motif.
+/// * Returned monomer intervals are shifted back into target coordinates.
+/// * Without a motif length, coordinates are treated as linear.
+pub fn find_circular(
+    monomers: &Lapper<u32, Monomer>,
+    start: u32,
+    stop: u32,
+    motif_len: Option<u32>,
+) -> Vec<Interval<u32, &Monomer>> {
+    let Some(motif_len) = motif_len else {
+        return monomers
+            .find(start, stop)
+            .map(|m| Interval {
+                start: m.start,
+                stop: m.stop,
+                val: &m.val,
+            })
+            .collect();
+    };
+    if start >= stop {
+        return vec![];
+    }
+    let first_copy = start / motif_len;
+    let last_copy = (stop - 1) / motif_len;
+
+    let mut found = vec![];
+    for copy in first_copy..=last_copy {
+        let offset = copy * motif_len;
+        let copy_st = start.max(offset) - offset;
+        let copy_end = stop.min(offset + motif_len) - offset;
+        found.extend(monomers.find(copy_st, copy_end).map(|m| Interval {
+            start: m.start + offset,
+            stop: m.stop + offset,
+            val: &m.val,
+        }));
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use ordered_float::OrderedFloat;
+
+    use super::*;
+
+    /// Monomers at `10-20` and `90-100` of a 100 bp motif.
+    fn monomers() -> Lapper<u32, Monomer> {
+        Lapper::new(
+            [(10, 20, "m0"), (90, 100, "m1")]
+                .into_iter()
+                .map(|(start, stop, name)| Interval {
+                    start,
+                    stop,
+                    val: Monomer {
+                        srf_repeat: "prefix#circ1-100".to_owned(),
+                        trf_monomer: name.to_owned(),
+                        trf_period: 10,
+                        trf_copy_num: OrderedFloat(1.0),
+                    },
+                })
+                .collect(),
+        )
+    }
+
+    fn find(start: u32, stop: u32, motif_len: Option<u32>) -> Vec<(u32, u32)> {
+        let monomers = monomers();
+        let mut found: Vec<(u32, u32)> = find_circular(&monomers, start, stop, motif_len)
+            .into_iter()
+            .map(|m| (m.start, m.stop))
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_parse_motif_len() {
+        assert_eq!(parse_motif_len("prefix#circ2-1706"), Some(1706));
+        assert_eq!(parse_motif_len("prefix#circ2"), None);
+        assert_eq!(parse_motif_len("prefix#circ2-abc"), None);
+    }
+
+    #[test]
+    fn test_get_motif_len() {
+        let motif_lens = HashMap::from([("prefix#circ2".to_owned(), 1706)]);
+        assert_eq!(get_motif_len("prefix#circ2", Some(&motif_lens)), Some(1706));
+        assert_eq!(
+            get_motif_len("prefix#circ3-340", Some(&motif_lens)),
+            Some(340)
+        );
+        assert_eq!(get_motif_len("prefix#circ3", Some(&motif_lens)), None);
+        assert_eq!(get_motif_len("prefix#circ4-0", None), None);
+    }
+
+    #[test]
+    fn test_find_circular_wrapping() {
+        // Interval wraps past the end of the first copy of the motif.
+        assert_eq!(find(85, 115, Some(100)), [(90, 100), (110, 120)]);
+    }
+
+    #[test]
+    fn test_find_circular_multiple_copies() {
+        assert_eq!(
+            find(15, 215, Some(100)),
+            [(10, 20), (90, 100), (110, 120), (190, 200), (210, 220)]
+        );
+        assert_eq!(find(100, 100, Some(100)), []);
+    }
+
+    #[test]
+    fn test_find_circular_linear() {
+        // Without a motif length, coordinates past the motif find nothing.
+        assert_eq!(
+            find(85, 115, get_motif_len("prefix#circ1", None)),
+            [(90, 100)]
+        );
+        assert_eq!(find(110, 120, None), []);
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::BTreeMap;
+
+use eyre::ContextCompat;
+use itertools::Itertools;
+use paf::PafRecord;
+use rust_lapper::{Interval, Lapper};
+
+use crate::{
+    cigar::get_seq_divergence,
+    cli::{DivergenceMetric, OverlapScore},
+    filter::AlignmentFilter,
+    monomer::MonomerRow,
+};
+
+/// Score of an alignment used to resolve overlaps. Higher is better.
+pub fn get_overlap_score(
+    rec: &PafRecord,
+    score: OverlapScore,
+    div_metric: DivergenceMetric,
+) -> eyre::Result<f64> {
+    Ok(match score {
+        OverlapScore::As => *rec.as_().context("Record has no AS tag.")? as f64,
+        OverlapScore::Identity => 1.0 - get_seq_divergence(rec, div_metric)?,
+        OverlapScore::Coverage => AlignmentFilter::motif_coverage(rec) as f64,
+    })
+}
+
+/// Alignment competing for query bases.
+#[derive(Debug)]
+struct Candidate {
+    motif: String,
+    score: f64,
+    rows: Vec<MonomerRow>,
+}
+
+/// Other alignment overlapping a segment won by a candidate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Loser {
+    is_tie: bool,
+    score: f64,
+}
+
+/// Query segment won by a candidate along with the other motifs overlapping it.
+#[derive(Debug)]
+struct Segment {
+    start: u32,
+    stop: u32,
+    losers: BTreeMap<String, Loser>,
+}
+
+/// Resolve overlapping rows from alignments of different `srf` motifs.
+/// * Each query base is kept by the alignment with the highest score.
+/// * Ties are broken by motif name and then by order added.
+#[derive(Debug, Default)]
+pub struct OverlapResolver {
+    candidates: BTreeMap<String, Vec<Candidate>>,
+}
+
+impl OverlapResolver {
+    /// Add the rows of an alignment.
+    pub fn add(&mut self, rec: &PafRecord, score: f64, rows: Vec<MonomerRow>) {
+        if rows.is_empty() {
+            return;
+        }
+        self.candidates
+            .entry(rec.query_name().to_owned())
+            .or_default()
+            .push(Candidate {
+                motif: rec.target_name().to_owned(),
+                score,
+                rows,
+            });
+    }
+
+    /// Trim rows to the query bases their alignment wins. Rows are sorted by position.
+    /// * The overlap column lists other motifs over the row. ex. `tie:circ2-1706:0.99,lost:circ5-340:0.95`
+    /// * Trimmed copies are marked as partial.
+    pub fn resolve(self) -> Vec<MonomerRow> {
+        let mut resolved = vec![];
+        for candidates in self.candidates.into_values() {
+            let segments = Self::find_winning_segments(&candidates);
+            for (candidate, segments) in candidates.into_iter().zip(segments) {
+                for row in candidate.rows {
+                    for segment in segments.iter() {
+                        let (start, stop) =
+                            (row.start.max(segment.start), row.end.min(segment.stop));
+                        if start >= stop {
+                            continue;
+                        }
+                        let mut trimmed = row.clone();
+                        if let Some(copy) = trimmed.copy.as_mut() {
+                            copy.is_partial |= start != row.start || stop != row.end;
+                        }
+                        trimmed.start = start;
+                        trimmed.end = stop;
+                        trimmed.overlaps = Some(format_losers(&segment.losers));
+                        resolved.push(trimmed);
+                    }
+                }
+            }
+        }
+        resolved.sort_by(|a, b| {
+            a.chrom
+                .cmp(&b.chrom)
+                .then(a.start.cmp(&b.start))
+                .then(a.end.cmp(&b.end))
+        });
+        resolved
+    }
+
+    /// Find segments won by each candidate on a single query.
+    /// * Candidates span the merged intervals of their rows.
+    /// * Adjacent segments won by the same candidate are merged.
+    fn find_winning_segments(candidates: &[Candidate]) -> Vec<Vec<Segment>> {
+        let mut itvs = vec![];
+        for (idx, candidate) in candidates.iter().enumerate() {
+            let mut rows = Lapper::new(
+                candidate
+                    .rows
+                    .iter()
+                    .map(|row| Interval {
+                        start: row.start,
+                        stop: row.end,
+                        val: (),
+                    })
+                    .collect(),
+            );
+            rows.merge_overlaps();
+            itvs.extend(rows.iter().map(|itv| Interval {
+                start: itv.start,
+                stop: itv.stop,
+                val: idx,
+            }));
+        }
+        let breakpoints = itvs
+            .iter()
+            .flat_map(|itv| [itv.start, itv.stop])
+            .sorted()
+            .dedup()
+            .collect_vec();
+        let itvs = Lapper::new(itvs);
+
+        let mut segments: Vec<Vec<Segment>> = candidates.iter().map(|_| vec![]).collect();
+        for (start, stop) in breakpoints.into_iter().tuple_windows() {
+            let Some(winner) = itvs.find(start, stop).map(|itv| itv.val).max_by(|a, b| {
+                let (ca, cb) = (&candidates[*a], &candidates[*b]);
+                ca.score
+                    .total_cmp(&cb.score)
+                    .then(cb.motif.cmp(&ca.motif))
+                    .then(b.cmp(a))
+            }) else {
+                continue;
+            };
+            let winner_score = candidates[winner].score;
+            let mut losers: BTreeMap<String, Loser> = BTreeMap::new();
+            for idx in itvs.find(start, stop).map(|itv| itv.val) {
+                if idx == winner {
+                    continue;
+                }
+                let candidate = &candidates[idx];
+                let loser = Loser {
+                    is_tie: candidate.score == winner_score,
+                    score: candidate.score,
+                };
+                insert_loser(&mut losers, candidate.motif.clone(), loser);
+            }
+            match segments[winner].last_mut() {
+                Some(last) if last.stop == start => {
+                    last.stop = stop;
+                    for (motif, loser) in losers {
+                        insert_loser(&mut last.losers, motif, loser);
+                    }
+                }
+                _ => segments[winner].push(Segment {
+                    start,
+                    stop,
+                    losers,
+                }),
+            }
+        }
+        segments
+    }
+}
+
+/// Add a losing motif, keeping its best score.
+fn insert_loser(losers: &mut BTreeMap<String, Loser>, motif: String, loser: Loser) {
+    losers
+        .entry(motif)
+        .and_modify(|other| {
+            if loser.score > other.score {
+                *other = loser
+            }
+        })
+        .or_insert(loser);
+}
+
+/// Format other motifs overlapping a segment from highest to lowest score or `.` if none.
+fn format_losers(losers: &BTreeMap<String, Loser>) -> String {
+    if losers.is_empty() {
+        return String::from(".");
+    }
+    losers
+        .iter()
+        .sorted_by(|(m1, l1), (m2, l2)| l2.score.total_cmp(&l1.score).then(m1.cmp(m2)))
+        .map(|(motif, loser)| {
+            let status = if loser.is_tie { "tie" } else { "lost" };
+            // Round to avoid long floats. ex. `0.9991`
+            let score = (loser.score * 1e6).round() / 1e6;
+            format!("{status}:{motif}:{score}")
+        })
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::monomer::CopyColumns;
+
+    fn rec(motif: &str) -> PafRecord {
+        PafRecord::new(
+            "chr1".to_owned(),
+            1000,
+            0,
+            1000,
+            '+',
+            motif.to_owned(),
+            1000,
+            0,
+            1000,
+            1000,
+            1000,
+            60,
+            HashMap::new(),
+        )
+    }
+
+    fn row(start: u32, end: u32, monomers: &str) -> MonomerRow {
+        MonomerRow {
+            chrom: "chr1".to_owned(),
+            start,
+            end,
+            monomers: monomers.to_owned(),
+            strand: '+',
+            period_class: "170".to_owned(),
+            copy: Some(CopyColumns {
+                copy_idx: Some(0),
+                period: Some(170),
+                is_partial: false,
+            }),
+            local_cols: String::new(),
+            overlaps: None,
+        }
+    }
+
+    fn resolve(resolver: OverlapResolver) -> Vec<(u32, u32, String, bool, String)> {
+        resolver
+            .resolve()
+            .into_iter()
+            .map(|row| {
+                (
+                    row.start,
+                    row.end,
+                    row.monomers,
+                    row.copy.unwrap().is_partial,
+                    row.overlaps.unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_tie_break() {
+        let mut resolver = OverlapResolver::default();
+        // Ties go to the first motif by name and then to the first alignment added.
+        resolver.add(&rec("circ2-340"), 0.9, vec![row(0, 100, "m0")]);
+        resolver.add(&rec("circ1-170"), 0.9, vec![row(0, 100, "m1")]);
+        resolver.add(&rec("circ1-170"), 0.9, vec![row(0, 100, "m2")]);
+        assert_eq!(
+            resolve(resolver),
+            [(
+                0,
+                100,
+                String::from("m1"),
+                false,
+                String::from("tie:circ1-170:0.9,tie:circ2-340:0.9")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_resolve_trim_and_drop() {
+        let mut resolver = OverlapResolver::default();
+        resolver.add(&rec("circ1-170"), 0.99, vec![row(0, 100, "m0")]);
+        // Trimmed to the bases it wins.
+        resolver.add(&rec("circ2-340"), 0.95, vec![row(50, 150, "m1")]);
+        // Dropped as it wins no bases.
+        resolver.add(&rec("circ3-510"), 0.9, vec![row(20, 80, "m2")]);
+        assert_eq!(
+            resolve(resolver),
+            [
+                (
+                    0,
+                    100,
+                    String::from("m0"),
+                    false,
+                    String::from("lost:circ2-340:0.95,lost:circ3-510:0.9")
+                ),
+                (100, 150, String::from("m1"), true, String::from("."))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_partial_flag() {
+        let mut resolver = OverlapResolver::default();
+        resolver.add(&rec("circ1-170"), 0.99, vec![row(160, 200, "m0")]);
+        // Tiled copies of the losing alignment. The trimmed copy becomes partial and the last is dropped.
+        let mut partial = row(170, 190, "m1");
+        if let Some(copy) = partial.copy.as_mut() {
+            copy.is_partial = true;
+        }
+        resolver.add(
+            &rec("circ2-340"),
+            0.9,
+            vec![row(0, 100, "m1"), row(100, 170, "m1"), partial],
+        );
+        assert_eq!(
+            resolve(resolver),
+            [
+                (0, 100, String::from("m1"), false, String::from(".")),
+                (100, 160, String::from("m1"), true, String::from(".")),
+                (
+                    160,
+                    200,
+                    String::from("m0"),
+                    false,
+                    String::from("lost:circ2-340:0.9")
+                )
+            ]
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use std::{fmt, str::FromStr};
+
+use itertools::Itertools;
+use rust_lapper::{Interval, Lapper};
+
+use crate::cli::Preset;
+
+/// Satellite family with its monomer periods and allowed difference in period length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SatelliteFamily {
+    pub name: &'static str,
+    pub periods: &'static [u32],
+    pub diff: f32,
+}
+
+/// Satellite families used by presets.
+pub const SATELLITE_FAMILIES: &[SatelliteFamily] = &[
+    // ~171 bp monomers and HORs of 2 to 6 monomers.
+    SatelliteFamily {
+        name: "alpha",
+        periods: &[170, 340, 510, 680, 850, 1020],
+        diff: 0.02,
+    },
+    SatelliteFamily {
+        name: "hsat1a",
+        periods: &[42],
+        diff: 0.05,
+    },
+    SatelliteFamily {
+        name: "hsat1b",
+        periods: &[2500],
+        diff: 0.05,
+    },
+    // Diverged (CATTC)n with ~23-26 bp units.
+    SatelliteFamily {
+        name: "hsat2",
+        periods: &[23, 26],
+        diff: 0.05,
+    },
+    // (CATTC)n with 5 bp units.
+    SatelliteFamily {
+        name: "hsat3",
+        periods: &[5],
+        diff: 0.0,
+    },
+    SatelliteFamily {
+        name: "beta",
+        periods: &[68],
+        diff: 0.03,
+    },
+    SatelliteFamily {
+        name: "gamma",
+        periods: &[220],
+        diff: 0.03,
+    },
+];
+
+impl Preset {
+    /// Satellite families in the preset.
+    pub fn families(&self) -> impl Iterator<Item = &'static SatelliteFamily> {
+        let name = match self {
+            Preset::Alpha => Some("alpha"),
+            Preset::Hsat1a => Some("hsat1a"),
+            Preset::Hsat1b => Some("hsat1b"),
+            Preset::Hsat2 => Some("hsat2"),
+            Preset::Hsat3 => Some("hsat3"),
+            Preset::Beta => Some("beta"),
+            Preset::Gamma => Some("gamma"),
+            Preset::All => None,
+        };
+        SATELLITE_FAMILIES
+            .iter()
+            .filter(move |family| name.is_none_or(|name| family.name == name))
+    }
+}
+
+/// Allowed difference in monomer period length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tolerance {
+    /// Percent difference in period length. ex. `170:0.02`
+    Fraction(f32),
+    /// Difference in base pairs. ex. `42:3bp`
+    Bp(u32),
+    /// Inclusive range of periods. ex. `171-172`
+    Range { min: u32, max: u32 },
+}
+
+/// Monomer period with the allowed difference in period length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeriodSpec {
+    /// Period class. The midpoint for ranges.
+    pub period: u32,
+    /// Allowed difference. Uses the default difference if not given.
+    pub tolerance: Option<Tolerance>,
+    /// Satellite family, if any.
+    pub family: Option<String>,
+}
+
+impl FromStr for PeriodSpec {
+    type Err = String;
+
+    /// Parse a period specification. ex. `170`, `170:0.02`, `42:3bp`, or `171-172`
+    /// * May be prefixed by a satellite family. ex. `alpha=170:0.02`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (family, s) = match s.split_once('=') {
+            Some((family, s)) if !family.is_empty() => (Some(family.to_owned()), s),
+            Some(_) => return Err(format!("Invalid period ({s}). Missing family.")),
+            None => (None, s),
+        };
+        let parse_period = |period: &str| {
+            period
+                .trim()
+                .parse::<u32>()
+                .map_err(|err| format!("Invalid period ({period}): {err}"))
+        };
+        let (period, tolerance) = if let Some((min, max)) = s.split_once('-') {
+            let (min, max) = (parse_period(min)?, parse_period(max)?);
+            if min > max {
+                return Err(format!("Invalid period range ({s}). Start is after end."));
+            }
+            ((min + max) / 2, Some(Tolerance::Range { min, max }))
+        } else if let Some((period, tolerance)) = s.split_once(':') {
+            let tolerance = if let Some(bp) = tolerance.strip_suffix("bp") {
+                Tolerance::Bp(
+                    bp.parse()
+                        .map_err(|err| format!("Invalid difference ({tolerance}): {err}"))?,
+                )
+            } else {
+                let frac: f32 = tolerance
+                    .parse()
+                    .map_err(|err| format!("Invalid difference ({tolerance}): {err}"))?;
+                if !(0.0..1.0).contains(&frac) {
+                    return Err(format!(
+                        "Invalid difference ({tolerance}). Must be in [0, 1)."
+                    ));
+                }
+                Tolerance::Fraction(frac)
+            };
+            (parse_period(period)?, Some(tolerance))
+        } else {
+            (parse_period(s)?, None)
+        };
+        Ok(PeriodSpec {
+            period,
+            tolerance,
+            family,
+        })
+    }
+}
+
+impl fmt::Display for PeriodSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(family) = &self.family {
+            write!(f, "{family}=")?;
+        }
+        match self.tolerance {
+            None => write!(f, "{}", self.period),
+            Some(Tolerance::Fraction(frac)) => write!(f, "{}:{frac}", self.period),
+            Some(Tolerance::Bp(bp)) => write!(f, "{}:{bp}bp", self.period),
+            Some(Tolerance::Range { min, max }) => write!(f, "{min}-{max}"),
+        }
+    }
+}
+
+impl PeriodSpec {
+    /// Inclusive range of allowed periods for a multiple of the period.
+    /// * The allowed difference is scaled by the multiple.
+    /// * Percent differences are rounded to the nearest bp and are at least 1 bp unless `0`.
+    ///     * ex. `170:0.02` -> `3.4` -> `167..=173` and `42:0.02` -> `0.84` -> `41..=43`
+    pub fn range(&self, default_diff: f32, multiple: u32) -> (u32, u32) {
+        // Avoid rounding down halves due to floating point error. ex. `75 * 0.02`
+        const EPS: f64 = 1e-6;
+        let period = self.period * multiple;
+        let allowed_diff = match self.tolerance.unwrap_or(Tolerance::Fraction(default_diff)) {
+            Tolerance::Fraction(frac) if frac > 0.0 => {
+                ((period as f64 * frac as f64 + EPS).round() as u32).max(1)
+            }
+            Tolerance::Fraction(_) => 0,
+            Tolerance::Bp(bp) => bp * multiple,
+            Tolerance::Range { min, max } => return (min * multiple, max * multiple),
+        };
+        (period.saturating_sub(allowed_diff), period + allowed_diff)
+    }
+}
+
+/// Get period specifications from a preset or from sizes.
+pub fn get_period_specs(sizes: &[PeriodSpec], preset: Option<Preset>) -> Vec<PeriodSpec> {
+    let Some(preset) = preset else {
+        return sizes.to_vec();
+    };
+    preset
+        .families()
+        .flat_map(|family| {
+            family.periods.iter().map(|period| PeriodSpec {
+                period: *period,
+                tolerance: Some(Tolerance::Fraction(family.diff)),
+                family: Some(family.name.to_owned()),
+            })
+        })
+        .collect()
+}
+
+/// Get the period class of a group of monomers from the period class each matched.
+/// * Uses the period class matched by the most monomers. Ties go to the smaller period.
+pub fn get_period_class(periods: impl IntoIterator<Item = u32>) -> Option<u32> {
+    periods
+        .into_iter()
+        .counts()
+        .into_iter()
+        .max_by(|(p1, c1), (p2, c2)| c1.cmp(c2).then(p2.cmp(p1)))
+        .map(|(period, _)| period)
+}
+
+/// Integer multiple of a base period.
+/// * Ordered by multiple and then base period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Harmonic {
+    pub multiple: u32,
+    pub base: u32,
+}
+
+impl Harmonic {
+    /// Period class of the harmonic.
+    pub fn period(&self) -> u32 {
+        self.base * self.multiple
+    }
+}
+
+/// Allowed monomer period ranges.
+/// * Matches a monomer length to the period class of the range it falls within.
+/// * With harmonics, each period is a base unit and integer multiples of it are also matched.
+#[derive(Debug, Clone)]
+pub struct PeriodMatcher {
+    /// Inclusive ranges with the harmonic as the value.
+    ranges: Lapper<u32, Harmonic>,
+    specs: Vec<PeriodSpec>,
+    /// Largest multiple of each period.
+    harmonics: u32,
+}
+
+impl PeriodMatcher {
+    /// Create period ranges. Specifications without a difference use `default_diff`.
+    /// * Multiples of each period up to `harmonics` are included. `1` only includes the period.
+    pub fn new(specs: Vec<PeriodSpec>, default_diff: f32, harmonics: u32) -> Self {
+        let harmonics = harmonics.max(1);
+        let specs = specs
+            .into_iter()
+            .map(|spec| PeriodSpec {
+                tolerance: Some(spec.tolerance.unwrap_or(Tolerance::Fraction(default_diff))),
+                ..spec
+            })
+            .collect_vec();
+        let ranges = Lapper::new(
+            specs
+                .iter()
+                .cartesian_product(1..=harmonics)
+                .map(|(spec, multiple)| {
+                    let (min, max) = spec.range(default_diff, multiple);
+                    Interval {
+                        start: min,
+                        stop: max + 1,
+                        val: Harmonic {
+                            multiple,
+                            base: spec.period,
+                        },
+                    }
+                })
+                .collect(),
+        );
+        PeriodMatcher {
+            ranges,
+            specs,
+            harmonics,
+        }
+    }
+
+    /// Length is within any period range.
+    pub fn contains(&self, len: u32) -> bool {
+        self.ranges.count(len, len + 1) > 0
+    }
+
+    /// Get the harmonic of a monomer length.
+    /// * If multiple period ranges overlap, the smallest multiple and then the smallest base period is used.
+    pub fn harmonic(&self, len: u32) -> Option<Harmonic> {
+        self.ranges.find(len, len + 1).map(|p| p.val).min()
+    }
+
+    /// Get the period class of a monomer length. See [`PeriodMatcher::harmonic`].
+    pub fn period(&self, len: u32) -> Option<u32> {
+        self.harmonic(len).map(|harmonic| harmonic.period())
+    }
+
+    /// Smallest period class.
+    pub fn min_period(&self) -> Option<u32> {
+        self.specs.iter().map(|spec| spec.period).min()
+    }
+
+    /// Label a period class with its satellite family, if any. ex. `alpha_340`
+    /// * With harmonics, the period class is labeled by its base unit and multiple. ex. `alpha_170x2`
+    pub fn label(&self, period: u32) -> String {
+        let Some((spec, multiple)) = self
+            .specs
+            .iter()
+            .cartesian_product(1..=self.harmonics)
+            .filter(|(spec, multiple)| spec.period * multiple == period)
+            .min_by_key(|(_, multiple)| *multiple)
+        else {
+            return period.to_string();
+        };
+        let family = spec
+            .family
+            .as_ref()
+            .map(|family| format!("{family}_"))
+            .unwrap_or_default();
+        if self.harmonics > 1 {
+            format!("{family}{}x{multiple}", spec.period)
+        } else {
+            format!("{family}{period}")
+        }
+    }
+
+    /// Period class of a label from [`PeriodMatcher::label`]. ex. `alpha_170` -> `170`
+    pub fn period_from_label(&self, label: &str) -> Option<u32> {
+        self.specs
+            .iter()
+            .cartesian_product(1..=self.harmonics)
+            .map(|(spec, multiple)| spec.period * multiple)
+            .find(|period| self.label(*period) == label)
+    }
+}
+
+/// One period specification and multiple per line with its inclusive range. ex. `alpha_170 (170:0.02): 167-173`
+impl fmt::Display for PeriodMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self
+            .specs
+            .iter()
+            .cartesian_product(1..=self.harmonics)
+            .sorted_by_key(|(spec, multiple)| spec.period * multiple)
+            .map(|(spec, multiple)| {
+                let (min, max) = spec.range(0.0, multiple);
+                format!(
+                    "{} ({spec}): {min}-{max}",
+                    self.label(spec.period * multiple)
+                )
+            })
+            .join("\n");
+        write!(f, "{lines}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(s: &str) -> PeriodSpec {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(
+            spec("170"),
+            PeriodSpec {
+                period: 170,
+                tolerance: None,
+                family: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_fraction() {
+        assert_eq!(spec("170:0.02").tolerance, Some(Tolerance::Fraction(0.02)));
+    }
+
+    #[test]
+    fn test_parse_bp() {
+        assert_eq!(spec("42:3bp").tolerance, Some(Tolerance::Bp(3)));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let spec = spec("171-172");
+        assert_eq!(spec.period, 171);
+        assert_eq!(
+            spec.tolerance,
+            Some(Tolerance::Range { min: 171, max: 172 })
+        );
+    }
+
+    #[test]
+    fn test_parse_family() {
+        assert_eq!(
+            spec("alpha=170:0.02"),
+            PeriodSpec {
+                period: 170,
+                tolerance: Some(Tolerance::Fraction(0.02)),
+                family: Some(String::from("alpha"))
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for s in ["=170", "172-171", "170:1.5"] {
+            assert!(s.parse::<PeriodSpec>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(spec("170").range(0.02, 1), (167, 173));
+        assert_eq!(spec("170").range(0.02, 2), (333, 347));
+        assert_eq!(spec("42:3bp").range(0.02, 1), (39, 45));
+        assert_eq!(spec("171-172").range(0.02, 2), (342, 344));
+    }
+
+    #[test]
+    fn test_range_minimum_bp() {
+        // 0.84 bp rounds to 1 bp.
+        assert_eq!(spec("42").range(0.02, 1), (41, 43));
+        // 0.2 bp is at least 1 bp.
+        assert_eq!(spec("10:0.02").range(0.02, 1), (9, 11));
+        assert_eq!(spec("42:0").range(0.02, 1), (42, 42));
+    }
+
+    #[test]
+    fn test_range_float_error() {
+        // 50 * 0.02 and 75 * 0.02 are slightly less than 1 and 1.5 as floats.
+        assert_eq!(spec("50").range(0.02, 1), (49, 51));
+        assert_eq!(spec("75").range(0.02, 1), (73, 77));
+    }
+}
@@ -0,0 +1,649 @@
+use std::collections::{BTreeMap, HashSet};
+
+use eyre::bail;
+use itertools::Itertools;
+
+use crate::{
+    io::BedRecord,
+    monomer::{MonomerCounts, MonomerIndex},
+};
+
+/// BED interval with columns: `chrom, st, end, monomers, strand`
+/// * Monomers are stored as IDs from a [`MonomerIndex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BedInterval {
+    pub chrom: String,
+    pub start: u32,
+    pub end: u32,
+    pub monomers: MonomerCounts,
+    pub strand: char,
+}
+
+impl BedInterval {
+    pub fn new(rec: BedRecord, monomer_index: &MonomerIndex) -> Self {
+        BedInterval {
+            chrom: rec.chrom.to_owned(),
+            start: rec.start,
+            end: rec.end,
+            monomers: rec
+                .name
+                .map(|monomers| monomer_index.count(monomers.split(',')))
+                .unwrap_or_default(),
+            strand: rec.strand.unwrap_or('.'),
+        }
+    }
+}
+
+/// Region of merged BED intervals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub chrom: String,
+    pub start: u32,
+    pub end: u32,
+    pub monomers: MonomerCounts,
+    /// Number of merged intervals.
+    pub n_hits: usize,
+    /// Bases covered by merged intervals.
+    pub covered_bp: u64,
+    /// Bases covered by merged intervals per period class.
+    pub period_bp: BTreeMap<u32, u64>,
+    /// Number of merged intervals per strand.
+    pub strands: BTreeMap<char, usize>,
+    // End of coverage so far. Used to avoid counting overlapping intervals twice.
+    covered_end: u32,
+    period_covered_end: BTreeMap<u32, u32>,
+}
+
+impl Region {
+    pub fn new(itv: BedInterval) -> Self {
+        let mut region = Region {
+            chrom: itv.chrom.clone(),
+            start: itv.start,
+            end: itv.end,
+            monomers: MonomerCounts::default(),
+            n_hits: 0,
+            covered_bp: 0,
+            period_bp: BTreeMap::new(),
+            strands: BTreeMap::new(),
+            covered_end: itv.start,
+            period_covered_end: BTreeMap::new(),
+        };
+        region.add(itv);
+        region
+    }
+
+    /// Add an interval to the region. Intervals must be added in order of start.
+    pub fn add(&mut self, itv: BedInterval) {
+        self.end = self.end.max(itv.end);
+        self.n_hits += 1;
+        self.covered_bp += itv.end.saturating_sub(itv.start.max(self.covered_end)) as u64;
+        self.covered_end = self.covered_end.max(itv.end);
+
+        if let Some(period) = itv.monomers.period_class() {
+            let period_covered_end = self.period_covered_end.entry(period).or_insert(itv.start);
+            *self.period_bp.entry(period).or_default() +=
+                itv.end.saturating_sub(itv.start.max(*period_covered_end)) as u64;
+            *period_covered_end = (*period_covered_end).max(itv.end);
+        }
+        *self.strands.entry(itv.strand).or_default() += 1;
+        self.monomers.extend(&itv.monomers);
+    }
+
+    pub fn len(&self) -> u32 {
+        self.end - self.start
+    }
+
+    /// Fraction of the region covered by merged intervals.
+    pub fn frac_covered(&self) -> f32 {
+        if self.len() == 0 {
+            return 0.0;
+        }
+        self.covered_bp as f32 / self.len() as f32
+    }
+
+    /// Remove monomers that don't fall within monomer period range.
+    pub fn retain_monomers(&mut self) {
+        self.monomers.retain_in_period();
+    }
+
+    /// Summary columns:
+    /// `n_hits, covered_bp, frac_covered, n_monomers, period:bp, strand:n_hits`
+    pub fn summary_columns(&self) -> String {
+        let fmt_counts = |counts: String| {
+            if counts.is_empty() {
+                String::from(".")
+            } else {
+                counts
+            }
+        };
+        format!(
+            "{}\t{}\t{:.4}\t{}\t{}\t{}",
+            self.n_hits,
+            self.covered_bp,
+            self.frac_covered(),
+            self.monomers.len(),
+            fmt_counts(
+                self.period_bp
+                    .iter()
+                    .map(|(period, bp)| format!("{period}:{bp}"))
+                    .join(",")
+            ),
+            fmt_counts(
+                self.strands
+                    .iter()
+                    .map(|(strand, n)| format!("{strand}:{n}"))
+                    .join(",")
+            ),
+        )
+    }
+}
+
+/// Check that intervals are sorted by chrom and start.
+#[derive(Debug, Default)]
+pub struct SortedCheck {
+    prev: Option<(String, u32)>,
+    finished_chroms: HashSet<String>,
+}
+
+impl SortedCheck {
+    pub fn check(&mut self, itv: &BedInterval) -> eyre::Result<()> {
+        match self.prev.as_mut() {
+            Some((chrom, start)) if *chrom == itv.chrom => {
+                if itv.start < *start {
+                    bail!(
+                        "Input not sorted by start. {}:{} is after {}:{}.",
+                        itv.chrom,
+                        itv.start,
+                        chrom,
+                        start
+                    )
+                }
+                *start = itv.start;
+            }
+            _ => {
+                if self.finished_chroms.contains(&itv.chrom) {
+                    bail!(
+                        "Input not sorted by chrom. {} is not contiguous.",
+                        itv.chrom
+                    )
+                }
+                if let Some((chrom, _)) = self.prev.take() {
+                    self.finished_chroms.insert(chrom);
+                }
+                self.prev = Some((itv.chrom.clone(), itv.start));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rule applied to every merged region before it's output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionFilter {
+    /// Minimum region length in base pairs.
+    pub min_len: u32,
+    /// Require monomers within the monomer period range.
+    /// Monomers outside of the range are removed.
+    pub require_periods: bool,
+    /// Minimum number of merged intervals.
+    pub min_hits: usize,
+}
+
+impl RegionFilter {
+    /// Finalize a merged region. Returns `None` if the region doesn't pass the filter.
+    /// * Regions must be longer than `min_len`.
+    pub fn finalize(&self, mut region: Region) -> Option<Region> {
+        if self.require_periods {
+            // Filter monomers that don't fall within monomer period range.
+            region.retain_monomers();
+            if region.monomers.is_empty() {
+                return None;
+            }
+        }
+        (region.len() > self.min_len && region.n_hits >= self.min_hits).then_some(region)
+    }
+}
+
+/// Merge intervals sorted by chrom and start within some distance.
+/// * Only the currently open region is kept in memory.
+/// * Each merged region is finalized with a [`RegionFilter`].
+#[derive(Debug)]
+pub struct RegionMerger {
+    dst: u32,
+    filter: RegionFilter,
+    curr_region: Option<Region>,
+}
+
+impl RegionMerger {
+    pub fn new(dst: u32, filter: RegionFilter) -> Self {
+        RegionMerger {
+            dst,
+            filter,
+            curr_region: None,
+        }
+    }
+
+    /// Add an interval. Returns the previous region if the interval can't be merged into it and it passes the filter.
+    pub fn push(&mut self, itv: BedInterval) -> Option<Region> {
+        let Some(mut region) = self.curr_region.take() else {
+            self.curr_region = Some(Region::new(itv));
+            return None;
+        };
+        let dst_between = itv.start.saturating_sub(region.end);
+        // Must be same name and within distance.
+        if dst_between <= self.dst && region.chrom == itv.chrom {
+            region.add(itv);
+            self.curr_region = Some(region);
+            return None;
+        }
+        self.curr_region = Some(Region::new(itv));
+        self.filter.finalize(region)
+    }
+
+    /// Get the last region if it passes the filter.
+    pub fn finish(self) -> Option<Region> {
+        self.filter.finalize(self.curr_region?)
+    }
+}
+
+/// Split an interval by the period class of its monomers.
+/// * The interval is returned for every period class with at least one monomer in it.
+/// * Monomers without a period class are dropped.
+pub fn split_by_period(itv: BedInterval) -> impl Iterator<Item = (u32, BedInterval)> {
+    let mut period_monomers: BTreeMap<u32, MonomerCounts> = BTreeMap::new();
+    for (id, count) in itv.monomers.iter() {
+        let Some(period) = count.period else {
+            continue;
+        };
+        period_monomers
+            .entry(period)
+            .or_default()
+            .add(id, count.period, count.count);
+    }
+    period_monomers.into_iter().map(move |(period, monomers)| {
+        (
+            period,
+            BedInterval {
+                chrom: itv.chrom.clone(),
+                start: itv.start,
+                end: itv.end,
+                monomers,
+                strand: itv.strand,
+            },
+        )
+    })
+}
+
+/// Bases covered by non-overlapping, sorted intervals within `[start, stop)`.
+/// * `prefix_bp` is the cumulative covered bases before each interval.
+fn covered_bp(coverage: &[(u32, u32)], prefix_bp: &[u64], start: u32, stop: u32) -> u64 {
+    let first = coverage.partition_point(|(_, end)| *end <= start);
+    let last = coverage.partition_point(|(st, _)| *st < stop);
+    if first >= last {
+        return 0;
+    }
+    let mut bp = prefix_bp[last] - prefix_bp[first];
+    // Remove parts of edge intervals outside of range.
+    bp -= start.saturating_sub(coverage[first].0) as u64;
+    bp -= coverage[last - 1].1.saturating_sub(stop) as u64;
+    bp
+}
+
+/// Merge intervals sorted by chrom and start by the density of monomer hits.
+/// * Slides a window anchored at each hit and keeps windows where the fraction of bases covered by hits is at least `min_density`.
+/// * Overlapping dense windows are joined and trimmed back to the first and last supporting hit.
+/// * Each merged region is finalized with a [`RegionFilter`].
+/// * Intervals are buffered per chrom.
+///
+/// The fraction of bases covered by hits is given by [`Region::frac_covered`].
+pub fn merge_intervals_by_density(
+    intervals: impl IntoIterator<Item = BedInterval>,
+    window: u32,
+    min_density: f32,
+    filter: &RegionFilter,
+) -> Vec<Region> {
+    let window = window.max(1);
+    let mut final_intervals = vec![];
+    for (_, hits) in &intervals.into_iter().chunk_by(|itv| itv.chrom.clone()) {
+        let hits = hits.collect_vec();
+
+        // Collapse overlapping hits so bases aren't counted twice.
+        let mut coverage: Vec<(u32, u32)> = vec![];
+        for hit in hits.iter() {
+            match coverage.last_mut() {
+                Some((_, last_end)) if hit.start <= *last_end => {
+                    *last_end = (*last_end).max(hit.end)
+                }
+                _ => coverage.push((hit.start, hit.end)),
+            }
+        }
+        let prefix_bp = std::iter::once(0)
+            .chain(coverage.iter().scan(0, |total, (st, end)| {
+                *total += (end - st) as u64;
+                Some(*total)
+            }))
+            .collect_vec();
+
+        // Windows starting at each hit start and ending at each hit end.
+        let dense_windows = hits
+            .iter()
+            .flat_map(|hit| [hit.start, hit.end.saturating_sub(window)])
+            .sorted()
+            .dedup()
+            .filter(|st| {
+                let bp = covered_bp(&coverage, &prefix_bp, *st, st + window);
+                bp as f32 / window as f32 >= min_density
+            });
+        let mut segments: Vec<(u32, u32)> = vec![];
+        for st in dense_windows {
+            let end = st + window;
+            match segments.last_mut() {
+                Some((_, last_end)) if st <= *last_end => *last_end = (*last_end).max(end),
+                _ => segments.push((st, end)),
+            }
+        }
+
+        // Trim segments to supporting hits and join any that now overlap.
+        let mut trimmed: Vec<Region> = vec![];
+        let mut hits = hits.into_iter().peekable();
+        for (seg_st, seg_end) in segments {
+            let mut is_new_segment = true;
+            while let Some(hit) = hits.next_if(|hit| hit.start < seg_end) {
+                if hit.end <= seg_st {
+                    continue;
+                }
+                match trimmed.last_mut() {
+                    Some(region) if !is_new_segment || hit.start <= region.end => region.add(hit),
+                    _ => trimmed.push(Region::new(hit)),
+                }
+                is_new_segment = false;
+            }
+        }
+
+        final_intervals.extend(
+            trimmed
+                .into_iter()
+                .filter_map(|region| filter.finalize(region)),
+        );
+    }
+    final_intervals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::period::{PeriodMatcher, PeriodSpec};
+
+    /// Monomer in the `170` period class.
+    fn monomer() -> String {
+        "A".repeat(170)
+    }
+
+    fn period_matcher() -> PeriodMatcher {
+        PeriodMatcher::new(vec!["170".parse::<PeriodSpec>().unwrap()], 0.02, 1)
+    }
+
+    fn itv(index: &MonomerIndex, chrom: &str, start: u32, end: u32, monomer: &str) -> BedInterval {
+        BedInterval {
+            chrom: chrom.to_owned(),
+            start,
+            end,
+            monomers: index.count([monomer]),
+            strand: '+',
+        }
+    }
+
+    fn filter(min_len: u32) -> RegionFilter {
+        RegionFilter {
+            min_len,
+            require_periods: true,
+            min_hits: 1,
+        }
+    }
+
+    fn merge(filter: RegionFilter, itvs: Vec<BedInterval>) -> Vec<(String, u32, u32)> {
+        let mut merger = RegionMerger::new(10, filter);
+        let mut regions = vec![];
+        for itv in itvs {
+            regions.extend(merger.push(itv));
+        }
+        regions.extend(merger.finish());
+        regions
+            .into_iter()
+            .map(|region| (region.chrom, region.start, region.end))
+            .collect()
+    }
+
+    #[test]
+    fn test_finalize_single_row() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        let region = Region::new(itv(&index, "chr1", 0, 170, &monomer()));
+        assert!(filter(100).finalize(region.clone()).is_some());
+        assert!(filter(200).finalize(region).is_none());
+    }
+
+    #[test]
+    fn test_finalize_length_is_exclusive() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        let region = Region::new(itv(&index, "chr1", 0, 170, &monomer()));
+        assert!(filter(169).finalize(region.clone()).is_some());
+        assert!(filter(170).finalize(region).is_none());
+    }
+
+    #[test]
+    fn test_finalize_min_hits() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        let mut region = Region::new(itv(&index, "chr1", 0, 170, &monomer()));
+        let filter = RegionFilter {
+            min_hits: 2,
+            ..filter(0)
+        };
+        assert!(filter.finalize(region.clone()).is_none());
+        region.add(itv(&index, "chr1", 170, 340, &monomer()));
+        assert_eq!(filter.finalize(region).map(|region| region.n_hits), Some(2));
+    }
+
+    #[test]
+    fn test_finalize_require_periods() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        // Monomer outside of the period range.
+        let region = Region::new(itv(&index, "chr1", 0, 500, "ACGT"));
+        assert!(filter(0).finalize(region.clone()).is_none());
+
+        let filter = RegionFilter {
+            require_periods: false,
+            ..filter(0)
+        };
+        let region = filter.finalize(region).unwrap();
+        assert_eq!(region.monomers.len(), 1);
+    }
+
+    #[test]
+    fn test_merger_single_row() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        let regions = merge(filter(0), vec![itv(&index, "chr1", 0, 170, &monomer())]);
+        assert_eq!(regions, [(String::from("chr1"), 0, 170)]);
+    }
+
+    #[test]
+    fn test_merger_last_region_of_contig() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        let regions = merge(
+            filter(200),
+            vec![
+                itv(&index, "chr1", 0, 170, &monomer()),
+                itv(&index, "chr1", 175, 345, &monomer()),
+                // Too short. Last region of chr1 is finalized the same way as the first.
+                itv(&index, "chr1", 1000, 1170, &monomer()),
+                itv(&index, "chr2", 0, 170, &monomer()),
+                itv(&index, "chr2", 170, 340, &monomer()),
+            ],
+        );
+        assert_eq!(
+            regions,
+            [
+                (String::from("chr1"), 0, 345),
+                (String::from("chr2"), 0, 340)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merger_one_interval_contig() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        let itvs = vec![
+            itv(&index, "chr1", 0, 170, &monomer()),
+            itv(&index, "chr1", 170, 340, &monomer()),
+            itv(&index, "chr2", 0, 170, &monomer()),
+            itv(&index, "chr3", 0, 170, &monomer()),
+            itv(&index, "chr3", 170, 340, &monomer()),
+        ];
+        assert_eq!(
+            merge(filter(0), itvs.clone()),
+            [
+                (String::from("chr1"), 0, 340),
+                (String::from("chr2"), 0, 170),
+                (String::from("chr3"), 0, 340)
+            ]
+        );
+        assert_eq!(
+            merge(
+                RegionFilter {
+                    min_hits: 2,
+                    ..filter(0)
+                },
+                itvs
+            ),
+            [
+                (String::from("chr1"), 0, 340),
+                (String::from("chr3"), 0, 340)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merger_require_periods_off() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        let itvs = vec![
+            itv(&index, "chr1", 0, 500, "ACGT"),
+            itv(&index, "chr2", 0, 170, &monomer()),
+        ];
+        assert_eq!(
+            merge(filter(0), itvs.clone()),
+            [(String::from("chr2"), 0, 170)]
+        );
+        let no_periods = RegionFilter {
+            require_periods: false,
+            ..filter(0)
+        };
+        assert_eq!(
+            merge(no_periods, itvs),
+            [
+                (String::from("chr1"), 0, 500),
+                (String::from("chr2"), 0, 170)
+            ]
+        );
+    }
+
+    fn merge_by_density(
+        window: u32,
+        min_density: f32,
+        itvs: Vec<BedInterval>,
+    ) -> Vec<(String, u32, u32, usize)> {
+        merge_intervals_by_density(itvs, window, min_density, &filter(0))
+            .into_iter()
+            .map(|region| (region.chrom, region.start, region.end, region.n_hits))
+            .collect()
+    }
+
+    /// Hits of `len` bp every `step` bp starting at `start`.
+    fn hits(
+        index: &MonomerIndex,
+        chrom: &str,
+        start: u32,
+        n: u32,
+        step: u32,
+        len: u32,
+    ) -> Vec<BedInterval> {
+        (0..n)
+            .map(|i| {
+                itv(
+                    index,
+                    chrom,
+                    start + i * step,
+                    start + i * step + len,
+                    &monomer(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_density_dense_run() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        // Region is trimmed to the first and last hit rather than the window.
+        assert_eq!(
+            merge_by_density(1000, 0.5, hits(&index, "chr1", 100, 10, 200, 170)),
+            [(String::from("chr1"), 100, 2070, 10)]
+        );
+    }
+
+    #[test]
+    fn test_density_sparse_gap() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        let mut itvs = hits(&index, "chr1", 0, 5, 200, 170);
+        // Single hit within the gap isn't dense enough to join the two runs.
+        itvs.push(itv(&index, "chr1", 3000, 3170, &monomer()));
+        itvs.extend(hits(&index, "chr1", 5000, 5, 200, 170));
+        assert_eq!(
+            merge_by_density(1000, 0.5, itvs),
+            [
+                (String::from("chr1"), 0, 970, 5),
+                (String::from("chr1"), 5000, 5970, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_density_window_larger_than_contig() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        // Density is relative to the full window even past the end of the contig.
+        let itvs = hits(&index, "chr1", 0, 2, 200, 170);
+        assert_eq!(merge_by_density(10_000, 0.5, itvs.clone()), []);
+        assert_eq!(
+            merge_by_density(10_000, 0.03, itvs),
+            [(String::from("chr1"), 0, 370, 2)]
+        );
+    }
+
+    #[test]
+    fn test_density_hit_straddling_segment_start() {
+        let matcher = period_matcher();
+        let index = MonomerIndex::new(&matcher);
+        // Hit longer than the window gives the segments 0-100 and 400-550.
+        // The second hit starts within the first and is joined to its region.
+        let itvs = vec![
+            itv(&index, "chr1", 0, 500, &monomer()),
+            itv(&index, "chr1", 450, 520, &monomer()),
+            itv(&index, "chr2", 0, 170, &monomer()),
+        ];
+        assert_eq!(
+            merge_by_density(100, 0.5, itvs),
+            [
+                (String::from("chr1"), 0, 520, 2),
+                (String::from("chr2"), 0, 170, 1)
+            ]
+        );
+    }
+}
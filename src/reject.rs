@@ -0,0 +1,94 @@
+use std::{collections::BTreeMap, fmt::Display, io::Write};
+
+use crate::filter::FilterReason;
+
+pub const REJECTED_TSV_HEADER: &str = "chrom\tst\tend\tmotif\treason\tvalues";
+
+/// Reason a PAF record or aligned interval was discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// Record removed by an alignment-level filter.
+    Filter(FilterReason),
+    /// Target motif has no `trf` monomers.
+    NoTrfMonomers,
+    /// Whole motif alignment is too divergent and none of its cigar blocks have monomers.
+    HighDivergence,
+    /// No monomers with a period in the given ranges.
+    NoPeriodMatch,
+    /// Aligned interval has no monomers on the motif.
+    NoMonomers,
+    /// Aligned interval is shorter than the periods of its monomers.
+    ShorterThanPeriod,
+    /// Local identity is less than `--min-local-identity`.
+    LowLocalIdentity,
+}
+
+impl RejectReason {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RejectReason::Filter(reason) => reason.code(),
+            RejectReason::NoTrfMonomers => "no_trf_monomers",
+            RejectReason::HighDivergence => "high_divergence",
+            RejectReason::NoPeriodMatch => "no_period_match",
+            RejectReason::NoMonomers => "no_monomers",
+            RejectReason::ShorterThanPeriod => "shorter_than_period",
+            RejectReason::LowLocalIdentity => "low_local_identity",
+        }
+    }
+}
+
+/// Discarded records and intervals with a tally per reason.
+/// * Rows are only written if a writer is given.
+pub struct Rejections {
+    writer: Option<Box<dyn Write>>,
+    counts: BTreeMap<&'static str, usize>,
+}
+
+impl Rejections {
+    pub fn new(mut writer: Option<Box<dyn Write>>) -> eyre::Result<Self> {
+        if let Some(writer) = writer.as_mut() {
+            writeln!(writer, "{REJECTED_TSV_HEADER}")?;
+        }
+        Ok(Rejections {
+            writer,
+            counts: BTreeMap::new(),
+        })
+    }
+
+    /// Record a discarded record or interval in query coordinates.
+    /// * `values` are only formatted if written. ex. `de=0.25`
+    pub fn reject(
+        &mut self,
+        chrom: &str,
+        start: u32,
+        end: u32,
+        motif: &str,
+        reason: RejectReason,
+        values: impl FnOnce() -> String,
+    ) -> eyre::Result<()> {
+        *self.counts.entry(reason.code()).or_default() += 1;
+        if let Some(writer) = self.writer.as_mut() {
+            writeln!(
+                writer,
+                "{chrom}\t{start}\t{end}\t{motif}\t{}\t{}",
+                reason.code(),
+                values()
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn counts(&self) -> &BTreeMap<&'static str, usize> {
+        &self.counts
+    }
+}
+
+impl Display for Rejections {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "reason\tcount")?;
+        for (reason, count) in self.counts.iter() {
+            write!(f, "\n{reason}\t{count}")?;
+        }
+        Ok(())
+    }
+}
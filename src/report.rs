@@ -0,0 +1,181 @@
+use std::{any::TypeId, collections::BTreeMap, ffi::OsStr, path::Path, time::Duration};
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    filter::{AlignmentFilter, FilterReason},
+    monomer::MonomerRow,
+    period::PeriodMatcher,
+    reject::Rejections,
+};
+
+/// Options that are input files.
+const INPUT_ARGS: [&str; 8] = [
+    "config",
+    "paf",
+    "monomers",
+    "fa",
+    "bed",
+    "assembly",
+    "fai",
+    "motif_fai",
+];
+
+/// Counts of PAF records read and removed.
+#[derive(Debug, Default, Serialize)]
+pub struct RecordCounts {
+    pub read: usize,
+    pub kept: usize,
+    /// Records removed by each alignment filter.
+    pub filtered: BTreeMap<String, usize>,
+    /// Records and aligned intervals discarded by reason.
+    pub rejected: BTreeMap<String, usize>,
+}
+
+impl RecordCounts {
+    pub fn new(aln_filter: &AlignmentFilter, rejections: &Rejections) -> Self {
+        RecordCounts {
+            read: aln_filter.n_total(),
+            kept: aln_filter.n_kept(),
+            filtered: FilterReason::ALL
+                .into_iter()
+                .map(|reason| (reason.option().to_owned(), aln_filter.n_removed(reason)))
+                .collect(),
+            rejected: rejections
+                .counts()
+                .iter()
+                .map(|(reason, count)| (reason.to_string(), *count))
+                .collect(),
+        }
+    }
+}
+
+/// Counts of BED rows read and regions, rows, or sequences written.
+#[derive(Debug, Default, Serialize)]
+pub struct RowCounts {
+    pub read: usize,
+    pub written: usize,
+}
+
+/// Machine-readable summary of a run.
+#[derive(Debug, Default, Serialize)]
+pub struct RunReport {
+    pub version: String,
+    pub command: String,
+    /// All options including defaults and those from a config.
+    pub params: BTreeMap<String, Value>,
+    pub inputs: BTreeMap<String, String>,
+    pub records: Option<RecordCounts>,
+    /// Counts of BED rows for `regions` and `extract`.
+    pub rows: Option<RowCounts>,
+    /// Base pairs of output rows or regions by period class. Rows without monomers of a period class are `.`.
+    pub bp_per_period_class: BTreeMap<String, u64>,
+    pub runtime_secs: f64,
+}
+
+impl RunReport {
+    pub fn new(cmd: &Command, matches: &ArgMatches) -> Self {
+        let mut report = RunReport {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            ..Default::default()
+        };
+        report.add_params(cmd, matches);
+        if let Some((subcommand, sub_matches)) = matches.subcommand() {
+            report.command = subcommand.to_owned();
+            if let Some(sub_cmd) = cmd.find_subcommand(subcommand) {
+                report.add_params(sub_cmd, sub_matches);
+            }
+        }
+        for (id, value) in report.params.iter() {
+            if let (true, Value::String(path)) = (INPUT_ARGS.contains(&id.as_str()), value) {
+                report.inputs.insert(id.clone(), path.clone());
+            }
+        }
+        report
+    }
+
+    fn add_params(&mut self, cmd: &Command, matches: &ArgMatches) {
+        for arg in cmd.get_arguments() {
+            if matches!(
+                arg.get_action(),
+                ArgAction::Help | ArgAction::HelpShort | ArgAction::HelpLong | ArgAction::Version
+            ) {
+                continue;
+            }
+            let id = arg.get_id().as_str();
+            let Ok(Some(values)) = matches.try_get_raw(id) else {
+                continue;
+            };
+            let values: Vec<Value> = values.map(|value| typed_value(arg, value)).collect();
+            let is_multiple = arg
+                .get_num_args()
+                .is_some_and(|num_args| num_args.max_values() > 1);
+            let value = match (is_multiple, <[Value; 1]>::try_from(values)) {
+                (false, Ok([value])) => value,
+                (_, Ok(values)) => Value::Array(values.into()),
+                (_, Err(values)) => Value::Array(values),
+            };
+            self.params.insert(id.to_owned(), value);
+        }
+    }
+
+    /// Add base pairs to a period class. `None` is labeled `.`.
+    pub fn add_bp(&mut self, period: Option<u32>, bp: u64, period_matcher: &PeriodMatcher) {
+        let label = period
+            .map(|period| period_matcher.label(period))
+            .unwrap_or_else(|| String::from("."));
+        *self.bp_per_period_class.entry(label).or_default() += bp;
+    }
+
+    /// Add the base pairs of an output row to its period class.
+    pub fn add_row_bp(&mut self, row: &MonomerRow) {
+        *self
+            .bp_per_period_class
+            .entry(row.period_class.clone())
+            .or_default() += (row.end - row.start) as u64;
+    }
+
+    /// Add a written region or sequence and its base pairs to its period class.
+    pub fn add_written(&mut self, period: Option<u32>, bp: u64, period_matcher: &PeriodMatcher) {
+        self.rows.get_or_insert_default().written += 1;
+        self.add_bp(period, bp, period_matcher);
+    }
+
+    /// Set the number of BED rows read.
+    pub fn set_rows_read(&mut self, read: usize) {
+        self.rows.get_or_insert_default().read = read;
+    }
+
+    pub fn write(&mut self, path: impl AsRef<Path>, runtime: Duration) -> eyre::Result<()> {
+        self.runtime_secs = runtime.as_secs_f64();
+        let writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+/// Convert a raw option value to a JSON value of its parsed type. ex. `0.02` as a number and `true` as a boolean
+fn typed_value(arg: &Arg, value: &OsStr) -> Value {
+    let value = value.to_string_lossy();
+    let type_id = arg.get_value_parser().type_id();
+    let is_number = [
+        TypeId::of::<u8>(),
+        TypeId::of::<u32>(),
+        TypeId::of::<u64>(),
+        TypeId::of::<usize>(),
+        TypeId::of::<f32>(),
+        TypeId::of::<f64>(),
+    ]
+    .into_iter()
+    .any(|number| type_id == number);
+    let typed = if type_id == TypeId::of::<bool>() {
+        value.parse().ok().map(Value::Bool)
+    } else if is_number {
+        value.parse().ok().map(Value::Number)
+    } else {
+        None
+    };
+    typed.unwrap_or_else(|| Value::String(value.into_owned()))
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+
+use crate::period::{Harmonic, PeriodSpec, Tolerance};
+
+/// Peak in a period spectrum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peak {
+    /// Period with the most base pairs.
+    pub period: u32,
+    /// Inclusive range of periods in the peak.
+    pub min: u32,
+    pub max: u32,
+    /// Base pairs of all periods in the peak.
+    pub bp: u64,
+    /// Smaller peak that this peak is a multiple of, if any.
+    pub harmonic: Option<Harmonic>,
+}
+
+/// Spectrum of `trf` periods weighted by aligned base pairs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PeriodSpectrum(BTreeMap<u32, u64>);
+
+pub const SPECTRUM_TSV_HEADER: &str = "period\tbp\tfrac_bp\tpeak";
+pub const PEAKS_TSV_HEADER: &str = "period\tmin\tmax\tbp\tfrac_bp\tbase\tmultiple";
+
+impl PeriodSpectrum {
+    pub fn add(&mut self, period: u32, bp: u64) {
+        *self.0.entry(period).or_default() += bp;
+    }
+
+    /// Total base pairs.
+    pub fn total_bp(&self) -> u64 {
+        self.0.values().sum()
+    }
+
+    /// Iterate through periods and their base pairs in order of period.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u64)> {
+        self.0.iter().map(|(period, bp)| (*period, *bp))
+    }
+
+    /// Find peaks in the spectrum.
+    /// * Periods are taken from most to least base pairs and claim all unclaimed periods within `diff` of them.
+    /// * Peaks with less than `min_frac` of the total base pairs are dropped.
+    /// * Peaks within `diff` of an integer multiple of a smaller peak are marked as harmonics. The smallest multiple is used.
+    ///     * Harmonics can't have more base pairs than their base peak or be more than `max_multiple` times it.
+    pub fn find_peaks(&self, diff: f32, min_frac: f32, max_multiple: u32) -> Vec<Peak> {
+        let total_bp = self.total_bp();
+        let mut claimed: BTreeMap<u32, bool> =
+            self.0.keys().map(|period| (*period, false)).collect();
+        let mut peaks = vec![];
+        for (period, _) in self
+            .0
+            .iter()
+            .sorted_by(|(p1, bp1), (p2, bp2)| bp2.cmp(bp1).then(p1.cmp(p2)))
+        {
+            if claimed[period] {
+                continue;
+            }
+            let (min, max) = period_spec(*period, diff).range(diff, 1);
+            let mut bp = 0;
+            for (other, is_claimed) in claimed.range_mut(min..=max) {
+                if !*is_claimed {
+                    *is_claimed = true;
+                    bp += self.0[other];
+                }
+            }
+            if total_bp == 0 || (bp as f32 / total_bp as f32) < min_frac {
+                continue;
+            }
+            peaks.push(Peak {
+                period: *period,
+                min,
+                max,
+                bp,
+                harmonic: None,
+            });
+        }
+        peaks.sort_by_key(|peak| peak.period);
+
+        // Check if peaks are multiples of smaller base peaks.
+        for i in 0..peaks.len() {
+            let (period, bp) = (peaks[i].period, peaks[i].bp);
+            peaks[i].harmonic = peaks[..i]
+                .iter()
+                .filter(|base| base.harmonic.is_none() && base.bp >= bp)
+                .filter_map(|base| {
+                    let multiple = (period as f32 / base.period as f32).round() as u32;
+                    let (min, max) = period_spec(base.period, diff).range(diff, multiple);
+                    ((2..=max_multiple).contains(&multiple) && (min..=max).contains(&period))
+                        .then_some(Harmonic {
+                            multiple,
+                            base: base.period,
+                        })
+                })
+                .min();
+        }
+        peaks
+    }
+}
+
+/// Period specification of a peak. ex. `171:0.02`
+pub fn period_spec(period: u32, diff: f32) -> PeriodSpec {
+    PeriodSpec {
+        period,
+        tolerance: Some(Tolerance::Fraction(diff)),
+        family: None,
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::BufRead,
+};
+
+use itertools::Itertools;
+use rust_lapper::{Interval, Lapper};
+use serde::Serialize;
+
+use crate::{io::parse_bed_line, monomer::get_bed_period_class, period::PeriodMatcher};
+
+/// Label for rows or totals not specific to a contig or period class.
+pub const ALL: &str = "all";
+/// Label for rows without any monomers in a period class.
+const UNCLASSIFIED: &str = ".";
+
+/// Intervals grouped by period class. Rows without a period class are `None`.
+type PeriodIntervals = BTreeMap<Option<u32>, Vec<Interval<u32, ()>>>;
+
+/// Summary of satellite content for a contig and period class.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SatelliteStats {
+    pub chrom: String,
+    pub period: String,
+    /// Total base pairs covered by arrays.
+    pub bp: u64,
+    /// Number of arrays after merging overlapping rows.
+    pub n_arrays: usize,
+    pub largest_array: u32,
+    pub n50: u32,
+    /// Fraction of the contig covered. Requires contig lengths.
+    pub frac_covered: Option<f64>,
+}
+
+impl SatelliteStats {
+    fn new(
+        chrom: &str,
+        period: &str,
+        array_lens: &[u32],
+        contig_len: Option<u64>,
+    ) -> SatelliteStats {
+        let bp = array_lens.iter().map(|len| *len as u64).sum();
+        SatelliteStats {
+            chrom: chrom.to_owned(),
+            period: period.to_owned(),
+            bp,
+            n_arrays: array_lens.len(),
+            largest_array: array_lens.iter().max().cloned().unwrap_or_default(),
+            n50: calculate_n50(array_lens),
+            frac_covered: contig_len
+                .filter(|len| *len != 0)
+                .map(|len| bp as f64 / len as f64),
+        }
+    }
+
+    pub fn to_tsv_row(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.chrom,
+            self.period,
+            self.bp,
+            self.n_arrays,
+            self.largest_array,
+            self.n50,
+            self.frac_covered
+                .map(|frac| format!("{frac:.6}"))
+                .unwrap_or_else(|| String::from("NA"))
+        )
+    }
+}
+
+pub const STATS_TSV_HEADER: &str = "chrom\tperiod\tbp\tn_arrays\tlargest_array\tn50\tfrac_covered";
+
+/// Calculate N50 of array lengths.
+pub fn calculate_n50(lengths: &[u32]) -> u32 {
+    let total: u64 = lengths.iter().map(|len| *len as u64).sum();
+    let mut cumulative = 0;
+    for len in lengths.iter().sorted_by(|a, b| b.cmp(a)) {
+        cumulative += *len as u64;
+        if cumulative * 2 >= total {
+            return *len;
+        }
+    }
+    0
+}
+
+fn period_label(period: Option<u32>, period_matcher: &PeriodMatcher) -> String {
+    period
+        .map(|period| period_matcher.label(period))
+        .unwrap_or_else(|| UNCLASSIFIED.to_owned())
+}
+
+/// Lengths of arrays after merging overlapping intervals.
+fn array_lengths(intervals: Vec<Interval<u32, ()>>) -> Vec<u32> {
+    let mut arrays = Lapper::new(intervals);
+    arrays.merge_overlaps();
+    arrays.iter().map(|itv| itv.stop - itv.start).collect()
+}
+
+/// Summarize satellite content per contig and period class from a BED file.
+/// * Rows are grouped by the period class of their monomers with a [`PeriodMatcher`].
+/// * Period classes are labeled by satellite family, if any.
+/// * Returns per-contig and period rows, per-contig totals, and per-period totals.
+pub fn summarize_bed(
+    reader: impl BufRead,
+    period_matcher: &PeriodMatcher,
+    contig_lens: Option<&HashMap<String, u32>>,
+) -> eyre::Result<Vec<SatelliteStats>> {
+    let mut contig_period_itvs: BTreeMap<String, PeriodIntervals> = BTreeMap::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let Some(rec) = parse_bed_line(&line, i + 1)? else {
+            continue;
+        };
+        let period = get_bed_period_class(&rec, period_matcher);
+        contig_period_itvs
+            .entry(rec.chrom.to_owned())
+            .or_default()
+            .entry(period)
+            .or_default()
+            .push(Interval {
+                start: rec.start,
+                stop: rec.end,
+                val: (),
+            });
+    }
+
+    let get_contig_len = |chrom: &str| {
+        contig_lens
+            .and_then(|lens| lens.get(chrom))
+            .map(|len| *len as u64)
+    };
+    let mut stats = vec![];
+    let mut period_array_lens: BTreeMap<Option<u32>, Vec<u32>> = BTreeMap::new();
+    for (chrom, period_itvs) in contig_period_itvs.iter() {
+        let contig_len = get_contig_len(chrom);
+        for (period, itvs) in period_itvs {
+            let array_lens = array_lengths(itvs.clone());
+            stats.push(SatelliteStats::new(
+                chrom,
+                &period_label(*period, period_matcher),
+                &array_lens,
+                contig_len,
+            ));
+            period_array_lens
+                .entry(*period)
+                .or_default()
+                .extend(array_lens);
+        }
+        // Merge across period classes so bp is not double-counted.
+        let contig_array_lens = array_lengths(period_itvs.values().flatten().cloned().collect());
+        stats.push(SatelliteStats::new(
+            chrom,
+            ALL,
+            &contig_array_lens,
+            contig_len,
+        ));
+    }
+
+    let total_len: Option<u64> = contig_lens.map(|lens| lens.values().map(|len| *len as u64).sum());
+    for (period, array_lens) in period_array_lens {
+        stats.push(SatelliteStats::new(
+            ALL,
+            &period_label(period, period_matcher),
+            &array_lens,
+            total_len,
+        ));
+    }
+    Ok(stats)
+}
@@ -0,0 +1,359 @@
+use itertools::Itertools;
+use ordered_float::OrderedFloat;
+use rust_lapper::{Interval, Lapper};
+
+use crate::io::{Monomer, MotifMonomers};
+
+/// Minimum length in base pairs of a tandem repeat and of the window used to score it.
+const MIN_REPEAT_LEN: usize = 30;
+/// Fraction of a tandem repeat that must overlap another to be considered the same repeat.
+const MIN_OVERLAP: f32 = 0.5;
+/// Gain in identity a repeat needs over a repeat of a period it's a multiple of to be kept. ex. HORs
+const MIN_IDENTITY_GAIN: f32 = 0.1;
+
+/// Tandem repeat on a motif.
+#[derive(Debug, Clone, PartialEq)]
+struct TandemRepeat {
+    start: usize,
+    end: usize,
+    period: usize,
+    /// Fraction of bases matching the next copy.
+    identity: f32,
+}
+
+impl TandemRepeat {
+    fn overlap(&self, start: usize, end: usize) -> usize {
+        self.end.min(end).saturating_sub(self.start.max(start))
+    }
+}
+
+/// Integer multiple of a base period if the period is within 2% or 1 bp of it. ex. `1` for neighboring periods
+fn multiple_of(period: usize, base: usize) -> Option<usize> {
+    let multiple = (period as f32 / base as f32).round() as usize;
+    let expected = base * multiple;
+    (multiple >= 1 && period.abs_diff(expected) as f32 <= (expected as f32 * 0.02).max(1.0))
+        .then_some(multiple)
+}
+
+/// Fraction of bases in `[start, end)` matching the base one period downstream, wrapping around the sequence.
+fn span_identity(seq: &[u8], period: usize, start: usize, end: usize) -> f32 {
+    if end <= start {
+        return 0.0;
+    }
+    let matches = (start..end)
+        .filter(|i| seq[*i].eq_ignore_ascii_case(&seq[(i + period) % seq.len()]))
+        .count();
+    matches as f32 / (end - start) as f32
+}
+
+/// Fraction of bases in `[start, end)` matching a base one period downstream, allowing a 1 bp shift from indels in the copies.
+fn shifted_span_identity(seq: &[u8], period: usize, start: usize, end: usize) -> f32 {
+    if end <= start {
+        return 0.0;
+    }
+    let matches = (start..end)
+        .filter(|i| {
+            (period.saturating_sub(1).max(1)..=period + 1)
+                .any(|offset| seq[*i].eq_ignore_ascii_case(&seq[(i + offset) % seq.len()]))
+        })
+        .count();
+    matches as f32 / (end - start) as f32
+}
+
+/// Overlapping tandem repeats with neighboring periods. ex. `169`, `170`, and `171`
+#[derive(Debug, Clone, PartialEq)]
+struct RepeatFamily {
+    start: usize,
+    end: usize,
+    periods: Vec<usize>,
+}
+
+impl RepeatFamily {
+    fn new(repeat: &TandemRepeat) -> Self {
+        RepeatFamily {
+            start: repeat.start,
+            end: repeat.end,
+            periods: vec![repeat.period],
+        }
+    }
+
+    /// Repeat mostly overlaps the family.
+    fn overlaps(&self, repeat: &TandemRepeat) -> bool {
+        repeat.overlap(self.start, self.end) as f32
+            >= (repeat.end - repeat.start) as f32 * MIN_OVERLAP
+    }
+
+    /// Family has a neighboring period and is within one period of this family.
+    fn is_neighbor(&self, other: &RepeatFamily) -> bool {
+        let max_period = self.periods.iter().chain(&other.periods).max().copied();
+        let gap = self
+            .start
+            .max(other.start)
+            .saturating_sub(self.end.min(other.end));
+        max_period.is_some_and(|max_period| gap <= max_period)
+            && self.periods.iter().any(|period| {
+                other
+                    .periods
+                    .iter()
+                    .any(|base| multiple_of(*period, *base) == Some(1))
+            })
+    }
+
+    fn merge(&mut self, other: RepeatFamily) {
+        self.start = self.start.min(other.start);
+        self.end = self.end.max(other.end);
+        for period in other.periods {
+            if !self.periods.contains(&period) {
+                self.periods.push(period);
+            }
+        }
+    }
+
+    fn add(&mut self, repeat: &TandemRepeat) {
+        self.start = self.start.min(repeat.start);
+        self.end = self.end.max(repeat.end);
+        if !self.periods.contains(&repeat.period) {
+            self.periods.push(repeat.period);
+        }
+    }
+
+    /// Repeat with the period that best matches its copies over the whole family.
+    fn best_repeat(&self, seq: &[u8]) -> TandemRepeat {
+        // Compare periods over the same bases.
+        let max_period = self.periods.iter().max().copied().unwrap_or_default();
+        let cmp_end = self.end.saturating_sub(max_period).max(self.start + 1);
+        let (period, identity) = self
+            .periods
+            .iter()
+            .map(|period| (*period, span_identity(seq, *period, self.start, cmp_end)))
+            .max_by(|(p1, i1), (p2, i2)| i1.total_cmp(i2).then(p2.cmp(p1)))
+            .unwrap_or_default();
+        TandemRepeat {
+            start: self.start,
+            end: self.end,
+            period,
+            identity,
+        }
+    }
+}
+
+/// Find tandem repeats of each period on a circular sequence.
+/// * Each base is compared to the base one period downstream, wrapping around the sequence.
+/// * Windows of at least one period where the fraction of matching bases is at least `min_identity` are merged into repeats of at least two copies.
+fn find_period_repeats(seq: &[u8], period: usize, min_identity: f32) -> Vec<TandemRepeat> {
+    let len = seq.len();
+    let window = period.max(MIN_REPEAT_LEN);
+    if window > len {
+        return vec![];
+    }
+    let prefix_matches = std::iter::once(0)
+        .chain((0..len).scan(0, |total, i| {
+            *total += usize::from(seq[i].eq_ignore_ascii_case(&seq[(i + period) % len]));
+            Some(*total)
+        }))
+        .collect_vec();
+    let identity = |st: usize, end: usize| {
+        (prefix_matches[end] - prefix_matches[st]) as f32 / (end - st) as f32
+    };
+
+    let mut spans: Vec<(usize, usize)> = vec![];
+    for st in 0..=(len - window) {
+        if identity(st, st + window) < min_identity {
+            continue;
+        }
+        // Window and the copy it was compared to.
+        let end = (st + window + period).min(len);
+        match spans.last_mut() {
+            Some((_, last_end)) if st <= *last_end => *last_end = (*last_end).max(end),
+            _ => spans.push((st, end)),
+        }
+    }
+    spans
+        .into_iter()
+        .filter(|(st, end)| end - st >= (2 * period).max(MIN_REPEAT_LEN))
+        .map(|(start, end)| TandemRepeat {
+            start,
+            end,
+            period,
+            identity: identity(start, end.saturating_sub(period).max(start + 1)),
+        })
+        .collect()
+}
+
+/// Consensus sequence of a tandem repeat from the most common base at each position of its copies.
+fn consensus(seq: &[u8], repeat: &TandemRepeat) -> String {
+    (0..repeat.period)
+        .map(|i| {
+            (repeat.start + i..repeat.end)
+                .step_by(repeat.period)
+                .map(|pos| seq[pos].to_ascii_uppercase())
+                .counts()
+                .into_iter()
+                .max_by(|(b1, c1), (b2, c2)| c1.cmp(c2).then(b2.cmp(b1)))
+                .map(|(base, _)| base as char)
+                .unwrap_or('N')
+        })
+        .collect()
+}
+
+/// Estimate `trf`-like monomers on a circular `srf` motif.
+/// * Tandem repeats are found for every period up to half the motif length.
+/// * From the smallest period, repeats that mostly overlap a family of repeats with a neighboring period join it.
+///     * Families with neighboring periods within a period of each other are merged.
+///     * Each family is reported once with the period that best matches its copies. ex. `170` rather than `169`, `170`, and `171`
+/// * Repeats that mostly overlap a family with a period they're a multiple of are dropped.
+///     * Multiples are only kept if they match their copies better than the base period over the same bases by [`MIN_IDENTITY_GAIN`]. ex. HORs
+///     * Bases may match one bp off the period so indels in the copies don't favor multiples.
+pub fn estimate_monomers(name: &str, seq: &[u8], min_identity: f32) -> Vec<Interval<u32, Monomer>> {
+    let mut families: Vec<RepeatFamily> = vec![];
+    for period in 1..=seq.len() / 2 {
+        for repeat in find_period_repeats(seq, period, min_identity) {
+            if let Some(family) = families.iter_mut().find(|family| {
+                family.overlaps(&repeat)
+                    && family
+                        .periods
+                        .iter()
+                        .any(|base| multiple_of(repeat.period, *base) == Some(1))
+            }) {
+                family.add(&repeat);
+                continue;
+            }
+            let cmp_end = repeat
+                .end
+                .saturating_sub(repeat.period)
+                .max(repeat.start + 1);
+            let is_redundant = families
+                .iter()
+                .filter(|family| family.overlaps(&repeat))
+                .flat_map(|family| family.periods.iter())
+                .filter(|base| multiple_of(repeat.period, **base).is_some_and(|m| m > 1))
+                .any(|base| {
+                    shifted_span_identity(seq, repeat.period, repeat.start, cmp_end)
+                        < shifted_span_identity(seq, *base, repeat.start, cmp_end)
+                            + MIN_IDENTITY_GAIN
+                });
+            if !is_redundant {
+                families.push(RepeatFamily::new(&repeat));
+            }
+        }
+    }
+    // Collapse families of neighboring periods split along the motif.
+    let mut merged: Vec<RepeatFamily> = vec![];
+    for family in families.into_iter().sorted_by_key(|family| family.start) {
+        match merged.iter_mut().find(|other| other.is_neighbor(&family)) {
+            Some(other) => other.merge(family),
+            None => merged.push(family),
+        }
+    }
+    merged
+        .iter()
+        .map(|family| family.best_repeat(seq))
+        .map(|repeat| Interval {
+            start: repeat.start as u32,
+            stop: repeat.end as u32,
+            val: Monomer {
+                srf_repeat: name.to_owned(),
+                trf_monomer: consensus(seq, &repeat),
+                trf_period: repeat.period as u32,
+                trf_copy_num: OrderedFloat(
+                    (repeat.end - repeat.start) as f32 / repeat.period as f32,
+                ),
+            },
+        })
+        .collect()
+}
+
+/// Estimate monomers on `srf` motifs. Motifs without tandem repeats are omitted.
+pub fn estimate_motif_monomers<'a>(
+    motifs: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+    min_identity: f32,
+) -> MotifMonomers {
+    motifs
+        .into_iter()
+        .filter_map(|(name, seq)| {
+            let monomers = estimate_monomers(name, seq, min_identity);
+            (!monomers.is_empty()).then(|| (name.to_owned(), Lapper::new(monomers)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Random sequence from a linear congruential generator.
+    fn random_seq(len: usize, mut state: u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                b"ACGT"[(state >> 62) as usize]
+            })
+            .collect()
+    }
+
+    /// Diverged copies of a 171 bp monomer with substitutions and single bp indels.
+    fn monomer_171_repeat(n_copies: usize) -> Vec<u8> {
+        let monomer = random_seq(171, 17);
+        let mut seq = vec![];
+        for copy in 0..n_copies {
+            let mut unit = monomer.clone();
+            for pos in [(copy * 37) % 171, (copy * 71 + 13) % 171] {
+                unit[pos] = if unit[pos] == b'A' { b'C' } else { b'A' };
+            }
+            match copy % 4 {
+                1 => {
+                    unit.remove((copy * 29) % 171);
+                }
+                3 => unit.insert((copy * 53) % 171, b'G'),
+                _ => (),
+            }
+            seq.extend(unit);
+        }
+        seq
+    }
+
+    #[test]
+    fn test_multiple_of() {
+        assert_eq!(multiple_of(171, 171), Some(1));
+        assert_eq!(multiple_of(170, 171), Some(1));
+        assert_eq!(multiple_of(342, 171), Some(2));
+        assert_eq!(multiple_of(511, 170), Some(3));
+        assert_eq!(multiple_of(180, 171), None);
+    }
+
+    #[test]
+    fn test_estimate_monomers_171() {
+        let seq = monomer_171_repeat(12);
+        let monomers = estimate_monomers("circ-171", &seq, 0.8);
+        let periods = monomers.iter().map(|itv| itv.val.trf_period).collect_vec();
+        assert_eq!(periods, [171]);
+        assert_eq!(monomers[0].val.trf_monomer.len(), 171);
+        assert!(monomers[0].stop - monomers[0].start >= 171 * 10);
+    }
+
+    #[test]
+    fn test_estimate_monomers_hor() {
+        // Identical HOR of two diverged 171 bp monomers.
+        let mut hor = random_seq(171, 17);
+        hor.extend(
+            hor.clone()
+                .into_iter()
+                .enumerate()
+                .map(|(i, base)| if i % 3 == 0 { b'T' } else { base }),
+        );
+        let seq = hor.repeat(6);
+        let periods = estimate_monomers("circ-342", &seq, 0.7)
+            .iter()
+            .map(|itv| itv.val.trf_period)
+            .collect_vec();
+        assert_eq!(periods, [171, 342]);
+    }
+
+    #[test]
+    fn test_estimate_monomers_no_repeat() {
+        let seq = random_seq(1000, 3);
+        assert!(estimate_monomers("circ-random", &seq, 0.8).is_empty());
+    }
+}
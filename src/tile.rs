@@ -0,0 +1,227 @@
+use rust_lapper::Interval;
+
+use crate::{
+    cigar::{CigarOp, get_query_offset},
+    io::Monomer,
+};
+
+/// Aligned block of a PAF record in query and target coordinates.
+#[derive(Debug, Clone)]
+pub struct AlignedBlock<'a> {
+    pub q_itv: Interval<u32, ()>,
+    pub t_itv: Interval<u32, ()>,
+    pub strand: char,
+    /// Cigar of a gapped block. Blocks without one are ungapped. ex. `=` or `X` blocks
+    pub cg_ops: Option<&'a [CigarOp]>,
+}
+
+impl AlignedBlock<'_> {
+    /// Offset of a target position from the query start of the block.
+    fn query_offset(&self, target_pos: u32) -> u32 {
+        match self.cg_ops {
+            Some(cg_ops) => get_query_offset(cg_ops, self.t_itv.start, target_pos),
+            None => target_pos - self.t_itv.start,
+        }
+    }
+}
+
+/// A single monomer copy within an aligned block in query coordinates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonomerCopy {
+    pub start: u32,
+    pub end: u32,
+    /// Index of copy relative to the start of the `trf` interval on the `srf` motif.
+    pub copy_idx: u32,
+    /// Copy is truncated by the edge of the aligned block or `trf` interval.
+    pub is_partial: bool,
+}
+
+/// Split an aligned block into individual monomer copies.
+/// * Uses the `trf` period and the phase of the `trf` interval on the `srf` motif to place copy boundaries in target space.
+/// * Boundaries are then projected onto the query through the block's cigar, if any, with respect to strand.
+pub fn tile_monomer_copies(
+    block: &AlignedBlock,
+    monomer: &Interval<u32, &Monomer>,
+) -> Vec<MonomerCopy> {
+    let (q_itv, t_itv) = (&block.q_itv, &block.t_itv);
+    let period = monomer.val.trf_period;
+    let ovl_st = t_itv.start.max(monomer.start);
+    let ovl_end = t_itv.stop.min(monomer.stop);
+    if period == 0 || ovl_st >= ovl_end {
+        return vec![];
+    }
+
+    let mut copies = vec![];
+    let mut copy_idx = (ovl_st - monomer.start) / period;
+    loop {
+        let copy_st = monomer.start + copy_idx * period;
+        if copy_st >= ovl_end {
+            break;
+        }
+        let t_st = copy_st.max(ovl_st);
+        let t_end = (copy_st + period).min(ovl_end);
+
+        // Project target offsets onto query. Reversed on the minus strand.
+        let (q_st_offset, q_end_offset) = (block.query_offset(t_st), block.query_offset(t_end));
+        let (q_st, q_end) = if block.strand == '-' {
+            (
+                q_itv.stop.saturating_sub(q_end_offset),
+                q_itv.stop.saturating_sub(q_st_offset),
+            )
+        } else {
+            (q_itv.start + q_st_offset, q_itv.start + q_end_offset)
+        };
+        let (q_st, q_end) = (
+            q_st.clamp(q_itv.start, q_itv.stop),
+            q_end.clamp(q_itv.start, q_itv.stop),
+        );
+        if q_st < q_end {
+            copies.push(MonomerCopy {
+                start: q_st,
+                end: q_end,
+                copy_idx,
+                is_partial: t_end - t_st < period,
+            });
+        }
+        copy_idx += 1;
+    }
+    copies
+}
+
+#[cfg(test)]
+mod tests {
+    use ordered_float::OrderedFloat;
+
+    use super::*;
+    use crate::cigar::parse_cigar;
+
+    fn monomer(period: u32) -> Monomer {
+        Monomer {
+            srf_repeat: "motif-30".to_owned(),
+            trf_monomer: "m0".to_owned(),
+            trf_period: period,
+            trf_copy_num: OrderedFloat(3.0),
+        }
+    }
+
+    fn itv(start: u32, stop: u32) -> Interval<u32, ()> {
+        Interval {
+            start,
+            stop,
+            val: (),
+        }
+    }
+
+    fn copies(
+        block: &AlignedBlock,
+        start: u32,
+        stop: u32,
+        period: u32,
+    ) -> Vec<(u32, u32, u32, bool)> {
+        let monomer = monomer(period);
+        let monomer_itv = Interval {
+            start,
+            stop,
+            val: &monomer,
+        };
+        tile_monomer_copies(block, &monomer_itv)
+            .into_iter()
+            .map(|copy| (copy.start, copy.end, copy.copy_idx, copy.is_partial))
+            .collect()
+    }
+
+    #[test]
+    fn test_tile_first_copy_phase() {
+        // Block starts 7 bp into the first copy of the trf interval.
+        let block = AlignedBlock {
+            q_itv: itv(100, 123),
+            t_itv: itv(12, 35),
+            strand: '+',
+            cg_ops: None,
+        };
+        assert_eq!(
+            copies(&block, 5, 35, 10),
+            [
+                (100, 103, 0, true),
+                (103, 113, 1, false),
+                (113, 123, 2, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tile_partial_copies_at_block_edges() {
+        let block = AlignedBlock {
+            q_itv: itv(100, 116),
+            t_itv: itv(12, 28),
+            strand: '+',
+            cg_ops: None,
+        };
+        assert_eq!(
+            copies(&block, 5, 35, 10),
+            [
+                (100, 103, 0, true),
+                (103, 113, 1, false),
+                (113, 116, 2, true)
+            ]
+        );
+        // Trf interval ends within the block.
+        let block = AlignedBlock {
+            q_itv: itv(100, 130),
+            t_itv: itv(0, 30),
+            strand: '+',
+            cg_ops: None,
+        };
+        assert_eq!(
+            copies(&block, 0, 25, 10),
+            [
+                (100, 110, 0, false),
+                (110, 120, 1, false),
+                (120, 125, 2, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tile_minus_strand() {
+        // Copies stay in target order but are reversed in query coordinates.
+        let block = AlignedBlock {
+            q_itv: itv(100, 125),
+            t_itv: itv(0, 25),
+            strand: '-',
+            cg_ops: None,
+        };
+        assert_eq!(
+            copies(&block, 0, 25, 10),
+            [
+                (115, 125, 0, false),
+                (105, 115, 1, false),
+                (100, 105, 2, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tile_through_cigar() {
+        // Query: 5 + 2 + 5 + 7 = 19 bp. Target: 5 + 5 + 3 + 7 = 20 bp.
+        let cg_ops = parse_cigar("5=2I5=3D7=").unwrap();
+        let block = AlignedBlock {
+            q_itv: itv(100, 119),
+            t_itv: itv(0, 20),
+            strand: '+',
+            cg_ops: Some(&cg_ops),
+        };
+        assert_eq!(
+            copies(&block, 0, 20, 10),
+            [(100, 112, 0, false), (112, 119, 1, false)]
+        );
+        let block = AlignedBlock {
+            strand: '-',
+            ..block
+        };
+        assert_eq!(
+            copies(&block, 0, 20, 10),
+            [(107, 119, 0, false), (100, 107, 1, false)]
+        );
+    }
+}